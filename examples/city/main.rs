@@ -0,0 +1,110 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{
+    Cube, FrustumCulling, Instances, VertexPullingPlugin, VertexPullingSettings,
+};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - city",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::rgb(0.5, 0.7, 0.9)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        // NOTE: From street level most buildings are hidden behind the nearest ones, so they are
+        // culled against the depth pyramid of the previous frame
+        .add_plugin(VertexPullingPlugin::<Cube>::new(VertexPullingSettings {
+            frustum_culling: FrustumCulling::Gpu,
+            self_occlusion_culling: true,
+            ..Default::default()
+        }))
+        .add_startup_system(setup)
+        .run();
+}
+
+/// Width of a block, between the streets around it.
+const BLOCK_SIZE: f32 = 60.0;
+const STREET_WIDTH: f32 = 15.0;
+/// Buildings along each side of a block.
+const BUILDINGS_PER_SIDE: usize = 3;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(0.5 * STREET_WIDTH, 2.0, 0.0)
+                .looking_at(Vec3::new(0.5 * STREET_WIDTH, 2.0, -100.0), Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+
+    let n_blocks = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(100);
+    info!(
+        "Generating {} blocks of {} buildings",
+        n_blocks * n_blocks,
+        BUILDINGS_PER_SIDE * BUILDINGS_PER_SIDE
+    );
+
+    let mut rng = rand::thread_rng();
+    let pitch = BLOCK_SIZE + STREET_WIDTH;
+    let extent = n_blocks as f32 * pitch;
+    let origin = Vec3::new(-0.5 * extent, 0.0, -0.5 * extent);
+    let building_size = BLOCK_SIZE / BUILDINGS_PER_SIDE as f32;
+
+    let mut cubes = Vec::with_capacity(n_blocks * n_blocks * BUILDINGS_PER_SIDE.pow(2) + 1);
+    cubes.push(Cube {
+        color: Color::DARK_GRAY,
+        center: Vec3::new(0.0, -0.5, 0.0),
+        half_extents: Vec3::new(0.5 * extent, 0.5, 0.5 * extent),
+    });
+    for block_x in 0..n_blocks {
+        for block_z in 0..n_blocks {
+            let block_corner = origin
+                + Vec3::new(
+                    block_x as f32 * pitch + STREET_WIDTH,
+                    0.0,
+                    block_z as f32 * pitch + STREET_WIDTH,
+                );
+            for x in 0..BUILDINGS_PER_SIDE {
+                for z in 0..BUILDINGS_PER_SIDE {
+                    let height = rng.gen_range(10.0..80.0);
+                    let half_extents =
+                        Vec3::new(0.45 * building_size, 0.5 * height, 0.45 * building_size);
+                    let grey = rng.gen_range(0.3..0.8);
+                    cubes.push(Cube {
+                        color: Color::rgb(grey, grey, grey + 0.05),
+                        center: block_corner
+                            + Vec3::new(
+                                (x as f32 + 0.5) * building_size,
+                                half_extents.y,
+                                (z as f32 + 0.5) * building_size,
+                            ),
+                        half_extents,
+                    });
+                }
+            }
+        }
+    }
+
+    commands.spawn_bundle((
+        Instances::new(cubes),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
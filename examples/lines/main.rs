@@ -0,0 +1,76 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{Instances, Line, LineCaps, VertexPullingPlugin, VertexPullingSettings};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - lines",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(VertexPullingPlugin::<Line>::new(
+            VertexPullingSettings::default(),
+        ))
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(Vec3::new(30.0, 20.0, 30.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+
+    let mut lines = vec![
+        Line {
+            color: Color::RED,
+            end: 10.0 * Vec3::X,
+            width: 4.0,
+            ..Default::default()
+        },
+        Line {
+            color: Color::GREEN,
+            end: 10.0 * Vec3::Y,
+            width: 4.0,
+            ..Default::default()
+        },
+        Line {
+            color: Color::BLUE,
+            end: 10.0 * Vec3::Z,
+            width: 4.0,
+            ..Default::default()
+        },
+    ];
+
+    // NOTE: A polyline with round joins between its segments
+    let helix = (0..=200)
+        .map(|i| {
+            let t = i as f32 * 0.1;
+            Vec3::new(8.0 * t.cos(), 0.5 * t - 5.0, 8.0 * t.sin())
+        })
+        .collect::<Vec<_>>();
+    lines.extend(Line::strip(&helix, 12.0, Color::GOLD, LineCaps::Round));
+
+    commands.spawn_bundle((
+        Instances::new(lines),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
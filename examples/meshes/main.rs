@@ -0,0 +1,98 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{
+    FrustumCulling, InstanceMeshes, Instances, MeshInstance, VertexPullingPlugin,
+    VertexPullingSettings,
+};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - meshes",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        // NOTE: Frustum culling tests the bounding box of each instance scaled by the radius of
+        // its mesh
+        .add_plugin(VertexPullingPlugin::<MeshInstance>::new(
+            VertexPullingSettings {
+                frustum_culling: FrustumCulling::Cpu,
+                ..Default::default()
+            },
+        ))
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 50.0, 150.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+
+    let n_instances = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(100_000);
+    info!("Generating {} mesh instances", n_instances);
+
+    // NOTE: All the instances of a few small meshes are drawn in a single draw, each with the index
+    // count of its own mesh, see InstanceMeshes.
+    let mesh_handles = vec![
+        meshes.add(Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 1,
+        })),
+        meshes.add(Mesh::from(shape::Torus {
+            radius: 1.0,
+            ring_radius: 0.4,
+            subdivisions_segments: 8,
+            subdivisions_sides: 5,
+        })),
+        meshes.add(Mesh::from(shape::Cube { size: 1.5 })),
+    ];
+    let mut rng = rand::thread_rng();
+    let instances: Vec<_> = (0..n_instances)
+        .map(|_| MeshInstance {
+            mesh: rng.gen_range(0..mesh_handles.len() as u32),
+            color: Color::rgb(
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+            ),
+            translation: Vec3::new(
+                rng.gen_range(-100.0..100.0),
+                rng.gen_range(-100.0..100.0),
+                rng.gen_range(-100.0..100.0),
+            ),
+            rotation: Quat::from_rotation_y(rng.gen_range(0.0..std::f32::consts::TAU)),
+            scale: rng.gen_range(0.5..1.5),
+        })
+        .collect();
+
+    commands.spawn_bundle((
+        Instances::new(instances),
+        InstanceMeshes {
+            meshes: mesh_handles,
+        },
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
@@ -0,0 +1,72 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{
+    Instances, Point, PointFootprint, VertexPullingPlugin, VertexPullingSettings,
+};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - points",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(VertexPullingPlugin::<Point>::new(
+            VertexPullingSettings::default(),
+        ))
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 30.0, 100.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+
+    let n_points = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(1_000_000);
+    info!("Generating {} points", n_points);
+
+    // NOTE: A rolling terrain sampled at random like a lidar scan, colored by height
+    let mut rng = rand::thread_rng();
+    let points = (0..n_points)
+        .map(|_| {
+            let x: f32 = rng.gen_range(-100.0..100.0);
+            let z: f32 = rng.gen_range(-100.0..100.0);
+            let y = 5.0 * (0.1 * x).sin() * (0.1 * z).cos();
+            Point {
+                color: Color::rgb(0.5 + 0.1 * y, 0.3, 0.5 - 0.1 * y),
+                position: Vec3::new(x, y, z),
+                size: 4,
+                footprint: PointFootprint::Round,
+                attenuation: true,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    commands.spawn_bundle((
+        Instances::new(points),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
@@ -0,0 +1,99 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{
+    Cylinder, Instances, Sphere, VertexPullingPlugin, VertexPullingSettings,
+};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - spheres",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(VertexPullingPlugin::<Sphere>::new(VertexPullingSettings {
+            depth_prepass: true,
+            ..Default::default()
+        }))
+        .add_plugin(VertexPullingPlugin::<Cylinder>::new(
+            VertexPullingSettings {
+                depth_prepass: true,
+                ..Default::default()
+            },
+        ))
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(100.0 * Vec3::Z).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+
+    let n_spheres = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(10_000);
+    info!("Generating {} spheres", n_spheres);
+
+    // NOTE: A random walk of atoms joined by bonds, overlapping so that the ray-cast depth is
+    // visible where they intersect
+    let mut rng = rand::thread_rng();
+    let mut position = Vec3::ZERO;
+    let mut spheres = Vec::with_capacity(n_spheres);
+    let mut cylinders = Vec::with_capacity(n_spheres);
+    for _ in 0..n_spheres {
+        let color = Color::rgb(
+            rng.gen_range(0.2..1.0),
+            rng.gen_range(0.2..1.0),
+            rng.gen_range(0.2..1.0),
+        );
+        spheres.push(Sphere {
+            color,
+            center: position,
+            radius: rng.gen_range(0.8..1.5),
+        });
+        let direction = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        )
+        .normalize_or_zero();
+        let next = (position + 3.0 * direction).clamp(-50.0 * Vec3::ONE, 50.0 * Vec3::ONE);
+        cylinders.push(Cylinder {
+            color: Color::GRAY,
+            start: position,
+            end: next,
+            radius: 0.3,
+        });
+        position = next;
+    }
+
+    commands.spawn_bundle((
+        Instances::new(spheres),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+    commands.spawn_bundle((
+        Instances::new(cylinders),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
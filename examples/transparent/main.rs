@@ -0,0 +1,91 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_vertex_pulling::{
+    InstanceSorting, Instances, Sphere, VertexPullingPlugin, VertexPullingSettings,
+};
+use examples_utils::camera::{CameraController, CameraControllerPlugin};
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: format!(
+                "{} {} - transparent",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            width: 1280.0,
+            height: 720.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(CameraControllerPlugin)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        // NOTE: The spheres are blended far to near, and their set is ordered with the transparent
+        // meshes in the Transparent3d phase
+        .add_plugin(VertexPullingPlugin::<Sphere>::new(VertexPullingSettings {
+            sorting: InstanceSorting::BackToFront,
+            ..Default::default()
+        }))
+        .add_startup_system(setup)
+        .run();
+}
+
+/// Spheres along each axis of the grid.
+const GRID_SIZE: usize = 10;
+const SPACING: f32 = 3.0;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(40.0, 30.0, 40.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(CameraController::default());
+    commands.spawn_bundle(PointLightBundle {
+        transform: Transform::from_xyz(20.0, 40.0, 20.0),
+        ..default()
+    });
+
+    // NOTE: An opaque mesh inside the grid, seen through the spheres
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: 10.0 })),
+        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
+        ..default()
+    });
+
+    let mut rng = rand::thread_rng();
+    let offset = 0.5 * (GRID_SIZE - 1) as f32 * SPACING;
+    let mut spheres = Vec::with_capacity(GRID_SIZE.pow(3));
+    for x in 0..GRID_SIZE {
+        for y in 0..GRID_SIZE {
+            for z in 0..GRID_SIZE {
+                let center = Vec3::new(x as f32, y as f32, z as f32) * SPACING - offset;
+                spheres.push(Sphere {
+                    color: Color::rgba(
+                        x as f32 / GRID_SIZE as f32,
+                        y as f32 / GRID_SIZE as f32,
+                        z as f32 / GRID_SIZE as f32,
+                        rng.gen_range(0.1..0.5),
+                    ),
+                    center,
+                    radius: 0.5 * SPACING * rng.gen_range(0.3..0.6),
+                });
+            }
+        }
+    }
+
+    commands.spawn_bundle((
+        Instances::new(spheres),
+        Transform::default(),
+        GlobalTransform::default(),
+    ));
+}
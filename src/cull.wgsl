@@ -0,0 +1,192 @@
+struct Cull {
+    planes: array<vec4<f32>, 5>;
+    occlusion_clip_from_local: mat4x4<f32>;
+    instance_count: u32;
+    vertices_per_instance: u32;
+    index_pattern_len: u32;
+    pyramid_mip_count: u32;
+    pyramid_size: vec2<f32>;
+    lod_quad_size: f32;
+    lod_point_size: f32;
+    lod_camera_position: vec3<f32>;
+    lod_pixels_per_unit: f32;
+    clip_from_local: mat4x4<f32>;
+    viewport_size: vec2<f32>;
+    software_raster_size: f32;
+};
+
+// NOTE: Must match LOD_IMPOSTOR_BIT and LOD_POINT_BIT
+let LOD_IMPOSTOR_BIT: u32 = 0x80000000u;
+let LOD_POINT_BIT: u32 = 0x40000000u;
+
+// NOTE: Must match LOD_QUAD_INDEX_PATTERN, the first three are LOD_POINT_INDEX_PATTERN
+var<private> lod_indices: array<u32, 6> = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+
+struct InstanceBounds {
+    center: vec3<f32>;
+    color: u32;
+    half_extents: vec4<f32>;
+};
+
+struct Bounds {
+    data: array<InstanceBounds>;
+};
+
+struct Indices {
+    data: array<u32>;
+};
+
+// NOTE: Must match the layout of wgpu's DrawIndexedIndirect
+struct DrawIndexedIndirect {
+    index_count: atomic<u32>;
+    instance_count: u32;
+    first_index: u32;
+    base_vertex: i32;
+    first_instance: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> cull: Cull;
+
+[[group(0), binding(1)]]
+var<storage> bounds: Bounds;
+
+[[group(0), binding(2)]]
+var<storage> index_pattern: Indices;
+
+[[group(0), binding(3)]]
+var<storage, read_write> indices: Indices;
+
+[[group(0), binding(4)]]
+var<storage, read_write> indirect: DrawIndexedIndirect;
+
+#ifdef OCCLUSION
+[[group(1), binding(0)]]
+var depth_pyramid: texture_2d<f32>;
+
+// NOTE: Depth is reversed so the nearest depth of the box is the largest and the farthest depth
+// of the pyramid texels is the smallest. The box is occluded when it is behind them.
+fn is_occluded(center: vec3<f32>, half_extents: vec3<f32>) -> bool {
+    var min_uv = vec2<f32>(1.0);
+    var max_uv = vec2<f32>(0.0);
+    var nearest_depth = 0.0;
+    for (var i: u32 = 0u; i < 8u; i = i + 1u) {
+        let corner = vec3<f32>(f32(i & 1u), f32((i >> 1u) & 1u), f32((i >> 2u) & 1u)) * 2.0 - 1.0;
+        let clip = cull.occlusion_clip_from_local * vec4<f32>(center + corner * half_extents, 1.0);
+        // NOTE: Boxes crossing the near plane cannot be projected so they are kept
+        if (clip.w <= 0.0) {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+        min_uv = min(min_uv, uv);
+        max_uv = max(max_uv, uv);
+        nearest_depth = max(nearest_depth, ndc.z);
+    }
+
+    // NOTE: The box spans at most 2x2 texels of the first mip whose texels are as large as it
+    let last_texel = textureDimensions(depth_pyramid, 0) - 1;
+    let first_mip_min = clamp(vec2<i32>(min_uv * cull.pyramid_size), vec2<i32>(0), last_texel);
+    let first_mip_max = clamp(vec2<i32>(max_uv * cull.pyramid_size), vec2<i32>(0), last_texel);
+    let span = vec2<f32>(first_mip_max - first_mip_min + 1);
+    let mip = min(u32(ceil(log2(max(span.x, span.y)))), cull.pyramid_mip_count - 1u);
+    // NOTE: Mip sizes are rounded down so the last texels of a mip also cover the remainder
+    let last_mip_texel = textureDimensions(depth_pyramid, i32(mip)) - 1;
+    let min_texel = min(first_mip_min >> vec2<u32>(mip), last_mip_texel);
+    let max_texel = min(first_mip_max >> vec2<u32>(mip), last_mip_texel);
+
+    let occluder_depth = min(
+        min(
+            textureLoad(depth_pyramid, min_texel, i32(mip)).r,
+            textureLoad(depth_pyramid, vec2<i32>(max_texel.x, min_texel.y), i32(mip)).r
+        ),
+        min(
+            textureLoad(depth_pyramid, vec2<i32>(min_texel.x, max_texel.y), i32(mip)).r,
+            textureLoad(depth_pyramid, max_texel, i32(mip)).r
+        )
+    );
+    return nearest_depth < occluder_depth;
+}
+#endif
+
+#ifdef SOFTWARE_RASTER
+// NOTE: Must match the unpacking in software_raster.wgsl
+struct Visibility {
+    data: array<atomic<u32>>;
+};
+
+[[group(0), binding(5)]]
+var<storage, read_write> visibility: Visibility;
+
+// NOTE: Each pixel packs the top 20 bits of the reversed depth above an RGB444 color. The bits of
+// non-negative floats sort in the same order as their values so atomicMax keeps the nearest
+// instance.
+fn rasterize(center: vec3<f32>, color: u32) {
+    let clip = cull.clip_from_local * vec4<f32>(center, 1.0);
+    if (clip.w <= 0.0) {
+        return;
+    }
+    let ndc = clip.xyz / clip.w;
+    let pixel = (ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * cull.viewport_size;
+    if (any(pixel < vec2<f32>(0.0)) || any(pixel >= cull.viewport_size)) {
+        return;
+    }
+
+    let depth = (bitcast<u32>(clamp(ndc.z, 0.0, 1.0)) << 1u) & 0xfffff000u;
+    let rgb = vec3<u32>(unpack4x8unorm(color).rgb * 15.0 + 0.5);
+    let index = u32(pixel.y) * u32(cull.viewport_size.x) + u32(pixel.x);
+    atomicMax(&visibility.data[index], depth | (rgb.r << 8u) | (rgb.g << 4u) | rgb.b);
+}
+#endif
+
+[[stage(compute), workgroup_size(64)]]
+fn cull_instances([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let slot = global_id.x;
+    if (slot >= cull.instance_count) {
+        return;
+    }
+
+    let instance_bounds = bounds.data[slot];
+    for (var i: i32 = 0; i < 5; i = i + 1) {
+        let plane = cull.planes[i];
+        let plane_distance = dot(plane.xyz, instance_bounds.center) + plane.w;
+        if (plane_distance < -dot(abs(plane.xyz), instance_bounds.half_extents.xyz)) {
+            return;
+        }
+    }
+#ifdef OCCLUSION
+    if (is_occluded(instance_bounds.center, instance_bounds.half_extents.xyz)) {
+        return;
+    }
+#endif
+
+    // NOTE: Impostor indices hold the slot instead of the first vertex so that they can address
+    // any shape
+    let lod_size = 2.0 * length(instance_bounds.half_extents.xyz) * cull.lod_pixels_per_unit
+        / max(distance(cull.lod_camera_position, instance_bounds.center), 1.0e-7);
+#ifdef SOFTWARE_RASTER
+    if (lod_size < cull.software_raster_size) {
+        rasterize(instance_bounds.center, instance_bounds.color);
+        return;
+    }
+#endif
+    if (lod_size < cull.lod_quad_size) {
+        var impostor = LOD_IMPOSTOR_BIT | (slot << 2u);
+        var index_count = 6u;
+        if (lod_size < cull.lod_point_size) {
+            impostor = impostor | LOD_POINT_BIT;
+            index_count = 3u;
+        }
+        let first_index = atomicAdd(&indirect.index_count, index_count);
+        for (var i: u32 = 0u; i < index_count; i = i + 1u) {
+            indices.data[first_index + i] = impostor | lod_indices[i];
+        }
+        return;
+    }
+
+    let first_index = atomicAdd(&indirect.index_count, cull.index_pattern_len);
+    let first_vertex = slot * cull.vertices_per_instance;
+    for (var i: u32 = 0u; i < cull.index_pattern_len; i = i + 1u) {
+        indices.data[first_index + i] = first_vertex + index_pattern.data[i];
+    }
+}
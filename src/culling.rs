@@ -0,0 +1,485 @@
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_graph::{self, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{
+            BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+            BindingType, BufferBindingType, BufferSize, CachedComputePipelineId,
+            ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, ShaderStages,
+            TextureSampleType, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{SoftwareRasterPipeline, SoftwareRasterTargets, SOFTWARE_RASTER_CLEAR_WORKGROUP_SIZE};
+
+pub const CULL_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 4718560287541391653);
+
+// NOTE: Must match the workgroup_size in cull.wgsl
+pub const CULL_WORKGROUP_SIZE: u32 = 64;
+
+/// Arguments of an indexed indirect draw with no indices, which the culling shader appends to.
+/// The fields are index count, instance count, first index, base vertex and first instance.
+pub const DRAW_INDEXED_INDIRECT_RESET: [u32; 5] = [0, 1, 0, 0, 0];
+
+pub(crate) fn load_shaders(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        CULL_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("cull.wgsl")),
+    );
+}
+
+/// Numbers of instances of shape `S` that passed and failed frustum culling in the last
+/// rendered frame, summed over all views. Only counted with [`FrustumCulling::Cpu`].
+///
+/// [`FrustumCulling::Cpu`]: crate::FrustumCulling::Cpu
+///
+/// The counters are shared between the main world and the render world so they can be read
+/// from main world systems, e.g. to feed a diagnostic.
+pub struct CullingStats<S> {
+    visible: Arc<AtomicUsize>,
+    culled: Arc<AtomicUsize>,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S> Clone for CullingStats<S> {
+    fn clone(&self) -> Self {
+        Self {
+            visible: self.visible.clone(),
+            culled: self.culled.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for CullingStats<S> {
+    fn default() -> Self {
+        Self {
+            visible: Default::default(),
+            culled: Default::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S> CullingStats<S> {
+    pub fn visible(&self) -> usize {
+        self.visible.load(Ordering::Relaxed)
+    }
+
+    pub fn culled(&self) -> usize {
+        self.culled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set(&self, visible: usize, culled: usize) {
+        self.visible.store(visible, Ordering::Relaxed);
+        self.culled.store(culled, Ordering::Relaxed);
+    }
+}
+
+/// Extracts the planes of the frustum of a `clip_from_local` matrix. Points inside the frustum
+/// are on the positive side of every plane.
+///
+/// The far plane is not included as Bevy uses an infinite reversed-z projection.
+pub fn frustum_planes(clip_from_local: Mat4) -> [Vec4; 5] {
+    let row0 = clip_from_local.row(0);
+    let row1 = clip_from_local.row(1);
+    let row2 = clip_from_local.row(2);
+    let row3 = clip_from_local.row(3);
+    [
+        row3 + row0,
+        row3 - row0,
+        row3 + row1,
+        row3 - row1,
+        // NOTE: Reversed-z puts the near plane at z == w
+        row3 - row2,
+    ]
+}
+
+/// Returns whether an axis-aligned box intersects the frustum described by `planes`.
+#[inline]
+pub fn aabb_in_frustum(planes: &[Vec4; 5], center: Vec3, half_extents: Vec3) -> bool {
+    planes.iter().all(|plane| {
+        let normal = plane.truncate();
+        normal.dot(center) + plane.w >= -normal.abs().dot(half_extents)
+    })
+}
+
+/// Bounding box of an instance as read by the culling compute shader.
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuInstanceBounds {
+    pub center: Vec3,
+    /// [`PulledShape::raster_color`](crate::PulledShape::raster_color) as RGBA8, only written
+    /// with software rasterization.
+    pub color: u32,
+    pub half_extents: Vec4,
+}
+
+/// Uniform of one dispatch of the culling compute shader.
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuCullUniform {
+    pub planes: [Vec4; 5],
+    /// Projects the instances into the depth pyramid of the view, only used by the occlusion
+    /// variant of the pipeline.
+    pub occlusion_clip_from_local: Mat4,
+    pub instance_count: u32,
+    pub vertices_per_instance: u32,
+    pub index_pattern_len: u32,
+    pub pyramid_mip_count: u32,
+    /// Size of the view in texels of the first mip of the depth pyramid.
+    pub pyramid_size: Vec2,
+    /// Screen-space sizes below which instances are drawn as impostors, zero without LOD.
+    pub lod_quad_size: f32,
+    pub lod_point_size: f32,
+    /// Position of the camera relative to the set.
+    pub lod_camera_position: Vec3,
+    pub lod_pixels_per_unit: f32,
+    /// Projects the instances into the visibility buffer of the view, only used by the software
+    /// rasterization variant of the pipeline.
+    pub clip_from_local: Mat4,
+    pub viewport_size: Vec2,
+    /// Screen-space size below which instances are software rasterized.
+    pub software_raster_size: f32,
+    _padding: u32,
+}
+
+impl GpuCullUniform {
+    pub fn new(
+        planes: [Vec4; 5],
+        instance_count: u32,
+        vertices_per_instance: u32,
+        index_pattern_len: u32,
+    ) -> Self {
+        Self {
+            planes,
+            occlusion_clip_from_local: Mat4::IDENTITY,
+            instance_count,
+            vertices_per_instance,
+            index_pattern_len,
+            pyramid_mip_count: 0,
+            pyramid_size: Vec2::ZERO,
+            lod_quad_size: 0.0,
+            lod_point_size: 0.0,
+            lod_camera_position: Vec3::ZERO,
+            lod_pixels_per_unit: 0.0,
+            clip_from_local: Mat4::IDENTITY,
+            viewport_size: Vec2::ZERO,
+            software_raster_size: 0.0,
+            _padding: 0,
+        }
+    }
+}
+
+/// Compute pipeline that culls the instances of a set against the frustum of a view, writing
+/// the indices of the visible ones and the arguments of an indirect draw. It only depends on
+/// the bounds of the instances so it is shared by all pulled shapes. The occlusion variant also
+/// tests the instances against the [`DepthPyramid`](crate::DepthPyramid) of the view, bound at
+/// group 1. The software rasterization variant binds the visibility buffer of a
+/// [`SoftwareRasterTarget`](crate::SoftwareRasterTarget) at group 0, binding 5.
+pub struct GpuCullingPipeline {
+    pub layout: BindGroupLayout,
+    pub software_raster_layout: BindGroupLayout,
+    pub occlusion_layout: BindGroupLayout,
+    pipeline_ids: [CachedComputePipelineId; 4],
+}
+
+impl GpuCullingPipeline {
+    pub fn pipeline_id(&self, occlusion: bool, software_raster: bool) -> CachedComputePipelineId {
+        self.pipeline_ids[occlusion as usize | (software_raster as usize) << 1]
+    }
+}
+
+impl FromWorld for GpuCullingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(0),
+            },
+            count: None,
+        };
+        let mut entries = vec![
+            // Cull uniform
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<GpuCullUniform>() as u64),
+                },
+                count: None,
+            },
+            // Bounds
+            storage(1, true),
+            // Index pattern
+            storage(2, true),
+            // Visible indices
+            storage(3, false),
+            // Indirect draw arguments
+            storage(4, false),
+        ];
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("vertex_pulling_cull_layout"),
+            entries: &entries,
+        });
+        // Visibility buffer
+        entries.push(storage(5, false));
+        let software_raster_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("vertex_pulling_cull_software_raster_layout"),
+                entries: &entries,
+            });
+
+        let occlusion_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("vertex_pulling_cull_occlusion_layout"),
+                    entries: &[
+                        // Depth pyramid
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Texture {
+                                sample_type: TextureSampleType::Float { filterable: false },
+                                view_dimension: TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_ids = [(false, false), (true, false), (false, true), (true, true)].map(
+            |(occlusion, software_raster)| {
+                let mut shader_defs = Vec::new();
+                let mut layouts = Vec::new();
+                if software_raster {
+                    shader_defs.push("SOFTWARE_RASTER".to_string());
+                    layouts.push(software_raster_layout.clone());
+                } else {
+                    layouts.push(layout.clone());
+                }
+                if occlusion {
+                    shader_defs.push("OCCLUSION".to_string());
+                    layouts.push(occlusion_layout.clone());
+                }
+                pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("vertex_pulling_cull_pipeline".into()),
+                    layout: Some(layouts),
+                    shader: CULL_SHADER_HANDLE.typed(),
+                    shader_defs,
+                    entry_point: "cull_instances".into(),
+                })
+            },
+        );
+
+        Self {
+            layout,
+            software_raster_layout,
+            occlusion_layout,
+            pipeline_ids,
+        }
+    }
+}
+
+/// A dispatch of the culling compute shader for one set of instances and one view.
+pub struct CullingDispatch {
+    pub view: Entity,
+    pub bind_group: BindGroup,
+    /// Depth pyramid of the view, when the set uses occlusion culling and the pyramid has been
+    /// built.
+    pub occlusion_bind_group: Option<BindGroup>,
+    /// Whether the bind group holds the visibility buffer of the view.
+    pub software_raster: bool,
+    pub workgroups: u32,
+}
+
+/// Culling dispatches of all pulled shapes for the current frame.
+#[derive(Default)]
+pub struct CullingDispatches {
+    pub dispatches: Vec<CullingDispatch>,
+}
+
+pub fn clear_culling_dispatches(mut culling_dispatches: ResMut<CullingDispatches>) {
+    culling_dispatches.dispatches.clear();
+}
+
+/// Runs the culling dispatches of a view before the instances are drawn.
+pub struct VertexPullingCullNode;
+
+impl VertexPullingCullNode {
+    pub const IN_VIEW: &'static str = "view";
+}
+
+impl render_graph::Node for VertexPullingCullNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(
+            VertexPullingCullNode::IN_VIEW,
+            SlotType::Entity,
+        )]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let culling_pipeline = world.resource::<GpuCullingPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        #[cfg(feature = "trace")]
+        let _vertex_pulling_cull_span = info_span!("vertex_pulling_cull").entered();
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("vertex_pulling_cull"),
+            });
+        // NOTE: The visibility buffer is cleared every frame before the instances are rasterized
+        // into it, even when none are, so that no stale pixels are resolved.
+        let software_raster_target = world
+            .get_resource::<SoftwareRasterTargets>()
+            .and_then(|targets| targets.targets.get(&view_entity));
+        if let Some(software_raster_target) = software_raster_target {
+            let software_raster_pipeline = world.resource::<SoftwareRasterPipeline>();
+            if let Some(pipeline) =
+                pipeline_cache.get_compute_pipeline(software_raster_pipeline.clear_pipeline_id)
+            {
+                let size = software_raster_target.size;
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &software_raster_target.bind_group, &[]);
+                pass.dispatch(
+                    (size.x + SOFTWARE_RASTER_CLEAR_WORKGROUP_SIZE - 1)
+                        / SOFTWARE_RASTER_CLEAR_WORKGROUP_SIZE,
+                    size.y,
+                    1,
+                );
+            }
+        }
+        for dispatch in world.resource::<CullingDispatches>().dispatches.iter() {
+            if dispatch.view != view_entity {
+                continue;
+            }
+            let pipeline_id =
+                |occlusion| culling_pipeline.pipeline_id(occlusion, dispatch.software_raster);
+            // NOTE: Until the occlusion variant is compiled the instances are only frustum
+            // culled.
+            let occlusion =
+                dispatch
+                    .occlusion_bind_group
+                    .as_ref()
+                    .and_then(|occlusion_bind_group| {
+                        pipeline_cache
+                            .get_compute_pipeline(pipeline_id(true))
+                            .map(|pipeline| (pipeline, occlusion_bind_group))
+                    });
+            match occlusion {
+                Some((pipeline, occlusion_bind_group)) => {
+                    pass.set_pipeline(pipeline);
+                    pass.set_bind_group(1, occlusion_bind_group, &[]);
+                }
+                None => match pipeline_cache.get_compute_pipeline(pipeline_id(false)) {
+                    Some(pipeline) => pass.set_pipeline(pipeline),
+                    None => continue,
+                },
+            }
+            pass.set_bind_group(0, &dispatch.bind_group, &[]);
+            pass.dispatch(dispatch.workgroups, 1, 1);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planes() -> [Vec4; 5] {
+        let projection =
+            Mat4::perspective_infinite_reverse_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1);
+        frustum_planes(projection)
+    }
+
+    #[test]
+    fn box_in_front_is_visible() {
+        let planes = planes();
+        assert!(aabb_in_frustum(
+            &planes,
+            Vec3::new(0.0, 0.0, -10.0),
+            Vec3::ONE
+        ));
+        // NOTE: The far plane is at infinity
+        assert!(aabb_in_frustum(
+            &planes,
+            Vec3::new(0.0, 0.0, -1.0e6),
+            Vec3::ONE
+        ));
+    }
+
+    #[test]
+    fn box_behind_is_culled() {
+        let planes = planes();
+        assert!(!aabb_in_frustum(
+            &planes,
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::ONE
+        ));
+        // Between the camera and the near plane
+        assert!(!aabb_in_frustum(
+            &planes,
+            Vec3::new(0.0, 0.0, -0.05),
+            Vec3::splat(0.01)
+        ));
+    }
+
+    #[test]
+    fn boxes_straddling_planes_are_visible() {
+        let planes = planes();
+        // With a 90 degree field of view and an aspect ratio of 1, the side planes at z = -10
+        // are at x = ±10 and y = ±10
+        for direction in [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y] {
+            let center = Vec3::new(0.0, 0.0, -10.0) + direction * 10.5;
+            assert!(
+                aabb_in_frustum(&planes, center, Vec3::ONE),
+                "{:?}",
+                direction
+            );
+            let center = Vec3::new(0.0, 0.0, -10.0) + direction * 13.0;
+            assert!(
+                !aabb_in_frustum(&planes, center, Vec3::ONE),
+                "{:?}",
+                direction
+            );
+        }
+        // Straddling the near plane
+        assert!(aabb_in_frustum(
+            &planes,
+            Vec3::new(0.0, 0.0, -0.1),
+            Vec3::splat(0.05)
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+#ifdef FIRST_MIP
+[[group(0), binding(0)]]
+var input: texture_depth_2d;
+#else
+[[group(0), binding(0)]]
+var input: texture_2d<f32>;
+#endif
+
+[[group(0), binding(1)]]
+var output: texture_storage_2d<r32float, write>;
+
+fn load_depth(coords: vec2<i32>) -> f32 {
+    let clamped = min(coords, textureDimensions(input) - 1);
+#ifdef FIRST_MIP
+    return textureLoad(input, clamped, 0);
+#else
+    return textureLoad(input, clamped, 0).r;
+#endif
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn downsample_depth([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+    if (any(coords >= textureDimensions(output))) {
+        return;
+    }
+
+    // NOTE: Depth is reversed so the farthest of the input texels has the smallest depth
+    let input_coords = coords * 2;
+    var depth = min(
+        min(load_depth(input_coords), load_depth(input_coords + vec2<i32>(1, 0))),
+        min(load_depth(input_coords + vec2<i32>(0, 1)), load_depth(input_coords + vec2<i32>(1, 1)))
+    );
+
+    // NOTE: Mip sizes are rounded down so with an odd input size the last output texels also
+    // cover the remaining column or row.
+    let input_size = textureDimensions(input);
+    let last_column = input_coords.x + 3 == input_size.x;
+    let last_row = input_coords.y + 3 == input_size.y;
+    if (last_column) {
+        depth = min(depth, min(load_depth(input_coords + vec2<i32>(2, 0)), load_depth(input_coords + vec2<i32>(2, 1))));
+    }
+    if (last_row) {
+        depth = min(depth, min(load_depth(input_coords + vec2<i32>(0, 2)), load_depth(input_coords + vec2<i32>(1, 2))));
+    }
+    if (last_column && last_row) {
+        depth = min(depth, load_depth(input_coords + vec2<i32>(2, 2)));
+    }
+    textureStore(output, coords, vec4<f32>(depth));
+}
@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::SetShadowViewBindGroup,
+    prelude::*,
+    render::{
+        render_phase::{
+            EntityRenderCommand, PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass,
+        },
+        render_resource::{IndexFormat, PipelineCache},
+    },
+};
+
+use crate::{
+    FrustumCulling, GpuInstances, IndexMode, InstanceStorage, PulledShape, VertexPullingPipeline,
+};
+
+pub type DrawInstances<S> = (
+    SetVertexPullingPipeline<S>,
+    SetShadowViewBindGroup<0>,
+    SetGpuInstancesBindGroup<S, 1>,
+    DrawVertexPulledInstances<S>,
+);
+
+/// Draws the instances into the depth buffer only, see
+/// [`VertexPullingSettings::depth_prepass`](crate::VertexPullingSettings::depth_prepass).
+pub type DrawInstancesPrepass<S> = (
+    SetVertexPullingPrepassPipeline<S>,
+    SetShadowViewBindGroup<0>,
+    SetGpuInstancesBindGroup<S, 1>,
+    DrawVertexPulledInstances<S>,
+);
+
+/// Draws the instances into the occluder depth of a [`DepthPyramid`](crate::DepthPyramid), see
+/// [`VertexPullingSettings::self_occlusion_culling`].
+///
+/// [`VertexPullingSettings::self_occlusion_culling`]: crate::VertexPullingSettings::self_occlusion_culling
+pub type DrawInstancesOcclusion<S> = (
+    SetVertexPullingOcclusionPipeline<S>,
+    SetShadowViewBindGroup<0>,
+    SetGpuInstancesBindGroup<S, 1>,
+    DrawVertexPulledInstances<S>,
+);
+
+pub struct SetVertexPullingPipeline<S>(PhantomData<fn() -> S>);
+impl<P: PhaseItem, S: PulledShape> RenderCommand<P> for SetVertexPullingPipeline<S> {
+    type Param = (SRes<PipelineCache>, SRes<VertexPullingPipeline<S>>);
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: &P,
+        params: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (pipeline_cache, vertex_pulling_pipeline) = params;
+        if let Some(pipeline) = pipeline_cache
+            .into_inner()
+            .get_render_pipeline(vertex_pulling_pipeline.pipeline_id)
+        {
+            pass.set_render_pipeline(pipeline);
+            RenderCommandResult::Success
+        } else {
+            RenderCommandResult::Failure
+        }
+    }
+}
+
+pub struct SetVertexPullingPrepassPipeline<S>(PhantomData<fn() -> S>);
+impl<P: PhaseItem, S: PulledShape> RenderCommand<P> for SetVertexPullingPrepassPipeline<S> {
+    type Param = (SRes<PipelineCache>, SRes<VertexPullingPipeline<S>>);
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: &P,
+        params: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (pipeline_cache, vertex_pulling_pipeline) = params;
+        if let Some(pipeline) = vertex_pulling_pipeline
+            .prepass_pipeline_id
+            .and_then(|id| pipeline_cache.into_inner().get_render_pipeline(id))
+        {
+            pass.set_render_pipeline(pipeline);
+            RenderCommandResult::Success
+        } else {
+            RenderCommandResult::Failure
+        }
+    }
+}
+
+pub struct SetVertexPullingOcclusionPipeline<S>(PhantomData<fn() -> S>);
+impl<P: PhaseItem, S: PulledShape> RenderCommand<P> for SetVertexPullingOcclusionPipeline<S> {
+    type Param = (SRes<PipelineCache>, SRes<VertexPullingPipeline<S>>);
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: &P,
+        params: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (pipeline_cache, vertex_pulling_pipeline) = params;
+        if let Some(pipeline) = vertex_pulling_pipeline
+            .occlusion_pipeline_id
+            .and_then(|id| pipeline_cache.into_inner().get_render_pipeline(id))
+        {
+            pass.set_render_pipeline(pipeline);
+            RenderCommandResult::Success
+        } else {
+            RenderCommandResult::Failure
+        }
+    }
+}
+
+pub struct SetGpuInstancesBindGroup<S, const I: usize>(PhantomData<fn() -> S>);
+impl<S: PulledShape, const I: usize> EntityRenderCommand for SetGpuInstancesBindGroup<S, I> {
+    type Param = SRes<GpuInstances<S>>;
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        gpu_instances: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bind_group = match gpu_instances
+            .into_inner()
+            .sets
+            .get(&item)
+            .and_then(|set| set.bind_group.as_ref())
+        {
+            Some(bind_group) => bind_group,
+            None => return RenderCommandResult::Failure,
+        };
+        pass.set_bind_group(I, bind_group, &[]);
+
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawVertexPulledInstances<S>(PhantomData<fn() -> S>);
+impl<S: PulledShape> EntityRenderCommand for DrawVertexPulledInstances<S> {
+    type Param = (SRes<GpuInstances<S>>, SRes<VertexPullingPipeline<S>>);
+
+    #[inline]
+    fn render<'w>(
+        view: Entity,
+        item: Entity,
+        (gpu_instances, pipeline): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let gpu_instances = gpu_instances.into_inner();
+        let set = match gpu_instances.sets.get(&item) {
+            Some(set) => set,
+            None => return RenderCommandResult::Failure,
+        };
+        // NOTE: An instance vertex buffer provides one instance per value so only the index
+        // pattern of a single instance is drawn.
+        let (index_count, instances) = match pipeline.settings.storage {
+            InstanceStorage::StorageBuffer | InstanceStorage::DataTexture => {
+                (set.index_count, 0..1)
+            }
+            InstanceStorage::VertexBuffer => {
+                let instance_buffer = match &set.instance_buffer {
+                    Some(instance_buffer) => instance_buffer,
+                    None => return RenderCommandResult::Failure,
+                };
+                pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                (S::INDEX_PATTERN.len() as u32, 0..set.len() as u32)
+            }
+        };
+        // NOTE: Instances with variants have no shared index buffer as the vertex shader finds
+        // their vertices, so they are only indexed by the view index buffers.
+        if S::VARIANTS && !pipeline.settings.view_index_buffers() {
+            pass.draw(0..index_count, instances);
+            return RenderCommandResult::Success;
+        }
+        match pipeline.settings.index_mode {
+            IndexMode::IndexBuffer => {
+                let frustum_culling = pipeline.settings.frustum_culling;
+                let (index_buffer, index_count) = if !pipeline.settings.view_index_buffers() {
+                    (&gpu_instances.index_buffer, index_count)
+                } else {
+                    match set.view_index_buffers.get(&view) {
+                        Some(view_index_buffer) => {
+                            (&view_index_buffer.buffer, view_index_buffer.index_count)
+                        }
+                        None => return RenderCommandResult::Failure,
+                    }
+                };
+                let index_buffer = match index_buffer {
+                    Some(index_buffer) => index_buffer,
+                    None if index_count == 0 => return RenderCommandResult::Success,
+                    None => return RenderCommandResult::Failure,
+                };
+                pass.set_index_buffer(index_buffer.slice(..), 0, IndexFormat::Uint32);
+                if frustum_culling == FrustumCulling::Gpu {
+                    let indirect_buffer = match set
+                        .view_index_buffers
+                        .get(&view)
+                        .and_then(|view_index_buffer| view_index_buffer.indirect_buffer.as_ref())
+                    {
+                        Some(indirect_buffer) => indirect_buffer,
+                        None => return RenderCommandResult::Failure,
+                    };
+                    pass.draw_indexed_indirect(indirect_buffer, 0);
+                } else if index_count > 0 {
+                    pass.draw_indexed(0..index_count, 0, instances);
+                }
+            }
+            IndexMode::Indexless => {
+                pass.draw(0..index_count, instances);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
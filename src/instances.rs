@@ -0,0 +1,1298 @@
+use std::{marker::PhantomData, num::NonZeroU32, ops::Range};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_phase::RenderPhase,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer,
+            BufferDescriptor, BufferInitDescriptor, BufferUsages, Extent3d, ImageCopyTexture,
+            ImageDataLayout, Origin3d, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+            TextureUsages, TextureView, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
+    },
+    utils::{HashMap, HashSet},
+};
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+
+use crate::{
+    aabb_in_frustum, frustum_planes, lod_level, lod_pixels_per_unit, radix_sort_indices,
+    CullingDispatch, CullingDispatches, CullingStats, DepthPyramid, DepthPyramids, FrustumCulling,
+    GpuCullUniform, GpuCullingPipeline, GpuInstanceBounds, IndexMode, InstanceSorting,
+    InstanceStorage, LodLevel, LodSettings, PulledShape, SoftwareRasterTarget,
+    SoftwareRasterTargets, VertexPullingPhaseItem, VertexPullingPipeline, VertexPullingSettings,
+    CULL_WORKGROUP_SIZE, DRAW_INDEXED_INDIRECT_RESET, LOD_IMPOSTOR_BIT, LOD_POINT_BIT,
+    LOD_POINT_INDEX_PATTERN, LOD_QUAD_INDEX_PATTERN,
+};
+
+/// Width in texels of the data texture used by [`InstanceStorage::DataTexture`]. Instances are
+/// stored row by row and the height grows with the number of instances.
+// NOTE: Must match data_texture_width in the shaders
+pub const DATA_TEXTURE_WIDTH: u32 = 2048;
+
+/// A set of instances drawn by [`VertexPullingPlugin<S>`](crate::VertexPullingPlugin).
+///
+/// Modifications are tracked as ranges of dirty instances so that only those ranges are
+/// uploaded to the GPU.
+#[derive(Clone, Component, Debug)]
+pub struct Instances<S> {
+    values: Vec<S>,
+    dirty: Vec<Range<usize>>,
+}
+
+impl<S> Default for Instances<S> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            dirty: Vec::new(),
+        }
+    }
+}
+
+impl<S> From<Vec<S>> for Instances<S> {
+    fn from(values: Vec<S>) -> Self {
+        Self::new(values)
+    }
+}
+
+impl<S> Instances<S> {
+    pub fn new(values: Vec<S>) -> Self {
+        let dirty = vec![0..values.len()];
+        Self { values, dirty }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[inline]
+    pub fn values(&self) -> &[S] {
+        &self.values
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&S> {
+        self.values.get(index)
+    }
+
+    /// Returns a mutable reference to the instance at `index` and marks it as dirty.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut S> {
+        if index < self.values.len() {
+            self.mark_dirty(index..index + 1);
+        }
+        self.values.get_mut(index)
+    }
+
+    pub fn set(&mut self, index: usize, value: S) {
+        self.values[index] = value;
+        self.mark_dirty(index..index + 1);
+    }
+
+    /// Returns the instances in `range` and marks them as dirty.
+    pub fn range_mut(&mut self, range: Range<usize>) -> &mut [S] {
+        self.mark_dirty(range.clone());
+        &mut self.values[range]
+    }
+
+    /// Returns all instances and marks them as dirty.
+    pub fn values_mut(&mut self) -> &mut [S] {
+        self.range_mut(0..self.values.len())
+    }
+
+    pub fn push(&mut self, value: S) {
+        self.values.push(value);
+        let len = self.values.len();
+        self.mark_dirty(len - 1..len);
+    }
+
+    pub fn extend<I: IntoIterator<Item = S>>(&mut self, values: I) {
+        let start = self.values.len();
+        self.values.extend(values);
+        self.mark_dirty(start..self.values.len());
+    }
+
+    /// Removes the instance at `index`, replacing it with the last instance. Only the moved
+    /// instance needs to be uploaded again.
+    pub fn swap_remove(&mut self, index: usize) -> S {
+        let value = self.values.swap_remove(index);
+        if index < self.values.len() {
+            self.mark_dirty(index..index + 1);
+        }
+        value
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.dirty.clear();
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        // NOTE: Sequential modifications are common so try to extend the last range before
+        // adding a new one.
+        if let Some(last) = self.dirty.last_mut() {
+            if range.start <= last.end && last.start <= range.end {
+                last.start = last.start.min(range.start);
+                last.end = last.end.max(range.end);
+                return;
+            }
+        }
+        self.dirty.push(range);
+    }
+
+    /// Takes the dirty ranges, sorted, merged and clamped to the current length.
+    fn take_dirty(&mut self) -> Vec<Range<usize>> {
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort_unstable_by_key(|range| range.start);
+
+        let len = self.values.len();
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(dirty.len());
+        for range in dirty {
+            let range = range.start.min(len)..range.end.min(len);
+            if range.is_empty() {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+/// Render world copy of the changes made to an [`Instances<S>`] since the previous frame.
+#[derive(Component)]
+pub struct ExtractedInstances<S> {
+    pub len: usize,
+    /// Offsets of modified ranges of instances with their new values.
+    pub changes: Vec<(usize, Vec<S>)>,
+    /// Transform of the entity, applied to all of its instances.
+    pub transform: Mat4,
+}
+
+pub fn extract_instances<S: PulledShape>(
+    mut commands: Commands,
+    mut instances: Query<(Entity, &mut Instances<S>, Option<&GlobalTransform>)>,
+) {
+    for (entity, mut instances, transform) in instances.iter_mut() {
+        let changes = if instances.is_changed() {
+            let dirty = instances.take_dirty();
+            dirty
+                .into_iter()
+                .map(|range| (range.start, instances.values[range].to_vec()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        commands.get_or_spawn(entity).insert(ExtractedInstances {
+            len: instances.len(),
+            changes,
+            transform: transform.map_or(Mat4::IDENTITY, GlobalTransform::compute_matrix),
+        });
+    }
+}
+
+/// Per-set uniform data, bound next to the instance buffer.
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuInstanceSetUniform {
+    pub model: Mat4,
+    pub inverse_model: Mat4,
+}
+
+/// Geometry of the variants that the instances of a set pick from with
+/// [`PulledShape::VARIANTS`], e.g. the meshes of a set of [`MeshInstance`](crate::MeshInstance)s.
+pub struct SetVariants {
+    /// Number of vertices each variant is drawn with.
+    pub vertex_counts: Vec<u32>,
+    /// Radius of the sphere around the origin of each variant, which scales
+    /// [`PulledShape::half_extents`] for frustum culling.
+    pub radii: Vec<f32>,
+    /// Bound from group 1, binding 3, as laid out by [`PulledShape::variant_layout_entries`].
+    pub buffers: Vec<Buffer>,
+}
+
+/// [`SetVariants`] of the sets of a shape with [`PulledShape::VARIANTS`], keyed by entity. They
+/// are prepared by the systems added by [`PulledShape::build`], before
+/// [`VertexPullingSystem::PrepareInstances`](crate::VertexPullingSystem::PrepareInstances).
+pub struct GpuSetVariants<S> {
+    pub sets: HashMap<Entity, SetVariants>,
+    /// Sets whose variants were added, replaced or removed this frame.
+    pub changed: HashSet<Entity>,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S> Default for GpuSetVariants<S> {
+    fn default() -> Self {
+        Self {
+            sets: HashMap::default(),
+            changed: HashSet::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Prefix sum of the vertex counts of the variants of the instances: the first vertex of each
+/// instance, followed by the number of vertices to draw. Instances of variants that the set
+/// does not have draw nothing.
+pub fn instance_first_vertices(variants: &[u32], vertex_counts: &[u32]) -> Vec<u32> {
+    let mut first_vertices = Vec::with_capacity(variants.len() + 1);
+    let mut first_vertex = 0;
+    for &variant in variants.iter() {
+        first_vertices.push(first_vertex);
+        first_vertex += vertex_counts.get(variant as usize).copied().unwrap_or(0);
+    }
+    first_vertices.push(first_vertex);
+    first_vertices
+}
+
+/// GPU storage of the instances of one entity.
+pub struct GpuInstanceSet<S: PulledShape> {
+    pub instance_buffer: Option<Buffer>,
+    /// Instance data with [`InstanceStorage::DataTexture`], used instead of the instance buffer.
+    pub instance_texture: Option<Texture>,
+    pub instance_texture_view: Option<TextureView>,
+    pub uniform_buffer: Option<Buffer>,
+    /// The first vertex of each instance with [`PulledShape::VARIANTS`], see
+    /// [`instance_first_vertices`]. Entries past the instances hold the vertex count so that the
+    /// binary search in the vertex shader never stops there.
+    pub first_vertex_buffer: Option<Buffer>,
+    /// Binds the instance buffer or texture and the uniform buffer, and the first vertex buffer
+    /// and the [`SetVariants`] with [`PulledShape::VARIANTS`]. It is only recreated when the
+    /// instance storage has to grow or the variants change.
+    pub bind_group: Option<BindGroup>,
+    /// Number of indices to draw, or of vertices when drawing without an index buffer.
+    pub index_count: u32,
+    /// CPU copy of the instance data, used to fill new storage when it has to grow.
+    values: Vec<S::Gpu>,
+    capacity: usize,
+    transform: Option<Mat4>,
+    /// Variant of each instance with [`PulledShape::VARIANTS`].
+    variants: Vec<u32>,
+    /// First vertex of each instance followed by the vertex count, with
+    /// [`PulledShape::VARIANTS`].
+    first_vertices: Vec<u32>,
+    /// [`SetVariants::radii`] of the set.
+    variant_radii: Vec<f32>,
+    /// Visible instances of each view in the order of the view, with frustum culling or sorting.
+    pub view_index_buffers: HashMap<Entity, ViewIndexBuffer>,
+    /// Bounds of the instances, read by the culling shader.
+    pub bounds_buffer: Option<Buffer>,
+    /// Centers of the instances, only kept up to date when the instances are sorted or culled.
+    centers: Vec<Vec3>,
+    /// Half extents of the instances, only kept up to date when the instances are culled.
+    half_extents: Vec<Vec3>,
+    /// Raster colors of the instances as RGBA8, only kept up to date with software
+    /// rasterization.
+    colors: Vec<u32>,
+}
+
+impl<S: PulledShape> Default for GpuInstanceSet<S> {
+    fn default() -> Self {
+        Self {
+            instance_buffer: None,
+            instance_texture: None,
+            instance_texture_view: None,
+            uniform_buffer: None,
+            first_vertex_buffer: None,
+            bind_group: None,
+            index_count: 0,
+            values: Vec::new(),
+            capacity: 0,
+            transform: None,
+            variants: Vec::new(),
+            first_vertices: Vec::new(),
+            variant_radii: Vec::new(),
+            view_index_buffers: HashMap::default(),
+            bounds_buffer: None,
+            centers: Vec::new(),
+            half_extents: Vec::new(),
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<S: PulledShape> GpuInstanceSet<S> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Applies the extracted changes and uploads the modified ranges. The whole set is uploaded
+    /// again if the storage has to grow. With [`PulledShape::VARIANTS`], `variants` are those of
+    /// the set, and `variants_changed` whether they changed this frame.
+    pub fn update(
+        &mut self,
+        extracted: &ExtractedInstances<S>,
+        variants: Option<&SetVariants>,
+        variants_changed: bool,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        pipeline: &VertexPullingPipeline<S>,
+    ) {
+        if self.transform != Some(extracted.transform) {
+            self.transform = Some(extracted.transform);
+            let uniform = GpuInstanceSetUniform {
+                model: extracted.transform,
+                inverse_model: extracted.transform.inverse(),
+            };
+            match &self.uniform_buffer {
+                Some(uniform_buffer) => {
+                    render_queue.write_buffer(uniform_buffer, 0, bytes_of(&uniform));
+                }
+                None => {
+                    self.uniform_buffer = Some(render_device.create_buffer_with_data(
+                        &BufferInitDescriptor {
+                            label: Some("gpu_instances_uniform_buffer"),
+                            contents: bytes_of(&uniform),
+                            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                        },
+                    ));
+                }
+            }
+        }
+
+        self.values.resize(extracted.len, S::Gpu::zeroed());
+        for (offset, values) in extracted.changes.iter() {
+            for (gpu, value) in self.values[*offset..].iter_mut().zip(values.iter()) {
+                *gpu = value.to_gpu();
+            }
+        }
+        self.index_count = (self.values.len() * S::INDEX_PATTERN.len()) as u32;
+        let first_vertices_changed = S::VARIANTS
+            && (variants_changed
+                || !extracted.changes.is_empty()
+                || self.variants.len() != extracted.len);
+        if first_vertices_changed {
+            self.variants.resize(extracted.len, 0);
+            for (offset, values) in extracted.changes.iter() {
+                for (variant, value) in self.variants[*offset..].iter_mut().zip(values.iter()) {
+                    *variant = value.variant();
+                }
+            }
+            let vertex_counts = variants.map_or(&[][..], |variants| &variants.vertex_counts);
+            self.first_vertices = instance_first_vertices(&self.variants, vertex_counts);
+            self.variant_radii = variants.map_or(Vec::new(), |variants| variants.radii.clone());
+        }
+        if S::VARIANTS {
+            self.index_count = self.first_vertices.last().copied().unwrap_or(0);
+        }
+
+        let settings = &pipeline.settings;
+        let culling = settings.frustum_culling != FrustumCulling::None;
+        if settings.sorting != InstanceSorting::None || culling {
+            self.centers.resize(extracted.len, Vec3::ZERO);
+            for (offset, values) in extracted.changes.iter() {
+                for (center, value) in self.centers[*offset..].iter_mut().zip(values.iter()) {
+                    *center = value.center();
+                }
+            }
+        }
+        if culling {
+            self.half_extents.resize(extracted.len, Vec3::ZERO);
+            for (offset, values) in extracted.changes.iter() {
+                for (half_extents, value) in
+                    self.half_extents[*offset..].iter_mut().zip(values.iter())
+                {
+                    *half_extents = value.half_extents();
+                }
+            }
+        }
+        if settings.lod.map_or(false, |lod| lod.software_raster()) {
+            self.colors.resize(extracted.len, 0);
+            for (offset, values) in extracted.changes.iter() {
+                for (color, value) in self.colors[*offset..].iter_mut().zip(values.iter()) {
+                    *color = value.raster_color().as_rgba_u32();
+                }
+            }
+        }
+
+        let grow = self.values.len() > self.capacity;
+        if grow {
+            self.capacity = self.values.len().next_power_of_two();
+            let storage = pipeline.settings.storage;
+            match storage {
+                InstanceStorage::StorageBuffer | InstanceStorage::VertexBuffer => {
+                    self.instance_buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                        label: Some("gpu_instances_instance_buffer"),
+                        size: (std::mem::size_of::<S::Gpu>() * self.capacity) as u64,
+                        usage: BufferUsages::COPY_DST
+                            | if storage == InstanceStorage::VertexBuffer {
+                                BufferUsages::VERTEX
+                            } else {
+                                BufferUsages::STORAGE
+                            },
+                        mapped_at_creation: false,
+                    }));
+                }
+                InstanceStorage::DataTexture => {
+                    let max_instances = max_data_texture_instances::<S>(render_device);
+                    assert!(
+                        self.values.len() <= max_instances,
+                        "{} instances do not fit in a data texture, the adapter supports at most {} of them",
+                        self.values.len(),
+                        max_instances
+                    );
+                    self.capacity = self.capacity.min(max_instances);
+                    let texels = self.capacity * texels_per_instance::<S>();
+                    let height = (texels as u32 + DATA_TEXTURE_WIDTH - 1) / DATA_TEXTURE_WIDTH;
+                    let texture = render_device.create_texture(&TextureDescriptor {
+                        label: Some("gpu_instances_instance_texture"),
+                        size: Extent3d {
+                            width: DATA_TEXTURE_WIDTH,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: S::DATA_TEXTURE_FORMAT,
+                        usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+                    });
+                    self.instance_texture_view =
+                        Some(texture.create_view(&TextureViewDescriptor::default()));
+                    self.instance_texture = Some(texture);
+                }
+            }
+
+            if pipeline.settings.frustum_culling == FrustumCulling::Gpu {
+                self.bounds_buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                    label: Some("gpu_instances_bounds_buffer"),
+                    size: (std::mem::size_of::<GpuInstanceBounds>() * self.capacity) as u64,
+                    usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }));
+            }
+            if S::VARIANTS {
+                self.first_vertex_buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                    label: Some("gpu_instances_first_vertex_buffer"),
+                    size: (std::mem::size_of::<u32>() * (self.capacity + 1)) as u64,
+                    usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }));
+            }
+        }
+        if grow || variants_changed {
+            self.create_bind_group(render_device, pipeline, variants);
+        }
+
+        if grow {
+            self.write_values(render_queue, 0, &self.values);
+            self.write_bounds(render_queue, 0, 0..self.values.len());
+        } else {
+            for (offset, values) in extracted.changes.iter() {
+                let range = *offset..*offset + values.len();
+                self.write_values(render_queue, *offset, &self.values[range.clone()]);
+                self.write_bounds(render_queue, *offset, range);
+            }
+        }
+        if grow || first_vertices_changed {
+            if let Some(first_vertex_buffer) = &self.first_vertex_buffer {
+                let mut first_vertices = self.first_vertices.clone();
+                first_vertices.resize(self.capacity + 1, self.index_count);
+                render_queue.write_buffer(first_vertex_buffer, 0, cast_slice(&first_vertices));
+            }
+        }
+    }
+
+    /// Binds the instance storage, the uniform buffer and, with [`PulledShape::VARIANTS`], the
+    /// first vertex buffer and the buffers of the variants. There is no bind group until they
+    /// all exist.
+    fn create_bind_group(
+        &mut self,
+        render_device: &RenderDevice,
+        pipeline: &VertexPullingPipeline<S>,
+        variants: Option<&SetVariants>,
+    ) {
+        self.bind_group = None;
+        let mut entries = Vec::new();
+        match pipeline.settings.storage {
+            InstanceStorage::StorageBuffer => match &self.instance_buffer {
+                Some(instance_buffer) => entries.push(BindGroupEntry {
+                    binding: 0,
+                    resource: instance_buffer.as_entire_binding(),
+                }),
+                None => return,
+            },
+            InstanceStorage::DataTexture => match &self.instance_texture_view {
+                Some(instance_texture_view) => entries.push(BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(instance_texture_view),
+                }),
+                None => return,
+            },
+            InstanceStorage::VertexBuffer => {}
+        }
+        match &self.uniform_buffer {
+            Some(uniform_buffer) => entries.push(BindGroupEntry {
+                binding: 1,
+                resource: uniform_buffer.as_entire_binding(),
+            }),
+            None => return,
+        }
+        if S::VARIANTS {
+            match (&self.first_vertex_buffer, variants) {
+                (Some(first_vertex_buffer), Some(variants)) => {
+                    entries.push(BindGroupEntry {
+                        binding: 2,
+                        resource: first_vertex_buffer.as_entire_binding(),
+                    });
+                    entries.extend(variants.buffers.iter().enumerate().map(|(i, buffer)| {
+                        BindGroupEntry {
+                            binding: 3 + i as u32,
+                            resource: buffer.as_entire_binding(),
+                        }
+                    }));
+                }
+                _ => return,
+            }
+        }
+        self.bind_group = Some(render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_instances_bind_group"),
+            layout: &pipeline.instances_layout,
+            entries: &entries,
+        }));
+    }
+
+    /// Writes the indices of the instances to the index buffer of the view, in the order of the
+    /// view when sorting. With [`FrustumCulling::Cpu`], the instances outside of the frustum of
+    /// the view are skipped and the others are written as impostors if they are small enough on
+    /// screen. Returns the number of visible instances.
+    pub fn update_view_indices(
+        &mut self,
+        view_entity: Entity,
+        view: &ExtractedView,
+        changed: bool,
+        settings: &VertexPullingSettings,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+    ) -> usize {
+        let world_from_local = self.transform.unwrap_or(Mat4::IDENTITY);
+        let camera_position = world_from_local
+            .inverse()
+            .transform_point3(view.transform.translation);
+        let view_index_buffer = self.view_index_buffers.entry(view_entity).or_default();
+        let sorted = view_index_buffer.sort(&self.centers, camera_position, changed, settings);
+        let culling = settings.frustum_culling == FrustumCulling::Cpu;
+        // NOTE: Without culling the indices only change with the order or number of instances,
+        // or with their variants
+        if !culling
+            && !sorted
+            && !(S::VARIANTS && changed)
+            && view_index_buffer.index_count == self.index_count
+        {
+            return self.values.len();
+        }
+
+        let planes = culling.then(|| {
+            frustum_planes(
+                view.projection * view.transform.compute_matrix().inverse() * world_from_local,
+            )
+        });
+        let (indices, visible) = self.view_indices(
+            &self.view_index_buffers[&view_entity].order,
+            planes.as_ref(),
+            settings.lod.as_ref(),
+            lod_pixels_per_unit(view),
+            camera_position,
+        );
+
+        let view_index_buffer = self.view_index_buffers.get_mut(&view_entity).unwrap();
+        view_index_buffer.index_count = indices.len() as u32;
+        if indices.len() > view_index_buffer.capacity {
+            view_index_buffer.capacity = indices.len().next_power_of_two();
+            let buffer = render_device.create_buffer(&BufferDescriptor {
+                label: Some("gpu_instances_view_index_buffer"),
+                size: (view_index_buffer.capacity * std::mem::size_of::<u32>()) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
+                mapped_at_creation: false,
+            });
+            render_queue.write_buffer(&buffer, 0, cast_slice(&indices));
+            view_index_buffer.buffer = Some(buffer);
+        } else if let Some(buffer) = &view_index_buffer.buffer {
+            render_queue.write_buffer(buffer, 0, cast_slice(&indices));
+        }
+        visible
+    }
+
+    /// Indices of the instances in `order`, or in the order of the set if it is empty, skipping
+    /// those outside of `planes` and picking their geometry with `lod`. Returns the indices and
+    /// the number of visible instances.
+    fn view_indices(
+        &self,
+        order: &[u32],
+        planes: Option<&[Vec4; 5]>,
+        lod: Option<&LodSettings>,
+        pixels_per_unit: f32,
+        camera_position: Vec3,
+    ) -> (Vec<u32>, usize) {
+        let mut visible = 0;
+        let mut indices = Vec::new();
+        for slot in 0..self.values.len() {
+            let index = order.get(slot).map_or(slot, |&index| index as usize);
+            // NOTE: The bounds are only stored with frustum culling, which LOD requires
+            let half_extents = || {
+                if S::VARIANTS {
+                    let variant = self.variants[index] as usize;
+                    self.half_extents[index]
+                        * self.variant_radii.get(variant).copied().unwrap_or(0.0)
+                } else {
+                    self.half_extents[index]
+                }
+            };
+            if let Some(planes) = planes {
+                if !aabb_in_frustum(planes, self.centers[index], half_extents()) {
+                    continue;
+                }
+            }
+            visible += 1;
+            let lod_level = lod.map_or(LodLevel::Full, |lod| {
+                let center = self.centers[index];
+                lod_level(
+                    lod,
+                    pixels_per_unit,
+                    camera_position,
+                    center,
+                    half_extents(),
+                )
+            });
+            let impostor = LOD_IMPOSTOR_BIT | (index as u32) << 2;
+            match lod_level {
+                // NOTE: The vertices of instances with variants are found by the vertex shader
+                LodLevel::Full if S::VARIANTS => {
+                    indices.extend(self.first_vertices[index]..self.first_vertices[index + 1]);
+                }
+                LodLevel::Full => {
+                    let first_vertex = index as u32 * S::VERTICES_PER_INSTANCE;
+                    indices.extend(S::INDEX_PATTERN.iter().map(|index| first_vertex + index));
+                }
+                LodLevel::Quad => {
+                    indices.extend(
+                        LOD_QUAD_INDEX_PATTERN
+                            .iter()
+                            .map(|corner| impostor | corner),
+                    );
+                }
+                LodLevel::Point => {
+                    indices.extend(
+                        LOD_POINT_INDEX_PATTERN
+                            .iter()
+                            .map(|corner| impostor | LOD_POINT_BIT | corner),
+                    );
+                }
+            }
+        }
+        (indices, visible)
+    }
+
+    /// Prepares the buffers of a view for the culling compute shader, which writes the indices of
+    /// the visible instances and the arguments of the indirect draw.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare_gpu_culling(
+        &mut self,
+        view_entity: Entity,
+        view: &ExtractedView,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        culling_pipeline: &GpuCullingPipeline,
+        index_pattern_buffer: &Buffer,
+        depth_pyramid: Option<&DepthPyramid>,
+        lod: Option<&LodSettings>,
+        software_raster_target: Option<&SoftwareRasterTarget>,
+    ) -> Option<CullingDispatch> {
+        let bounds_buffer = self.bounds_buffer.as_ref()?;
+        let world_from_local = self.transform.unwrap_or(Mat4::IDENTITY);
+        let planes = frustum_planes(
+            view.projection * view.transform.compute_matrix().inverse() * world_from_local,
+        );
+        let mut uniform = GpuCullUniform::new(
+            planes,
+            self.values.len() as u32,
+            S::VERTICES_PER_INSTANCE,
+            S::INDEX_PATTERN.len() as u32,
+        );
+        uniform.lod_camera_position = world_from_local
+            .inverse()
+            .transform_point3(view.transform.translation);
+        uniform.lod_pixels_per_unit = lod_pixels_per_unit(view);
+        // NOTE: The pyramid was built from the previous frame so the instances are projected
+        // with the view projection of that frame.
+        let occlusion_bind_group = depth_pyramid.and_then(|depth_pyramid| {
+            uniform.occlusion_clip_from_local = depth_pyramid.view_proj? * world_from_local;
+            uniform.pyramid_mip_count = depth_pyramid.mip_count();
+            uniform.pyramid_size = depth_pyramid.size.as_vec2() * 0.5;
+            Some(depth_pyramid.cull_bind_group.clone())
+        });
+        if let Some(lod) = lod {
+            uniform.lod_quad_size = lod.quad_size;
+            uniform.lod_point_size = lod.point_size;
+        }
+        let software_raster_target = software_raster_target.zip(lod);
+        if let Some((software_raster_target, lod)) = software_raster_target {
+            uniform.clip_from_local =
+                view.projection * view.transform.compute_matrix().inverse() * world_from_local;
+            uniform.viewport_size = software_raster_target.size.as_vec2();
+            uniform.software_raster_size = lod.software_raster_size;
+        }
+
+        let view_index_buffer = self.view_index_buffers.entry(view_entity).or_default();
+        let mut index_pattern_len = S::INDEX_PATTERN.len();
+        if lod.is_some() {
+            index_pattern_len = index_pattern_len.max(LOD_QUAD_INDEX_PATTERN.len());
+        }
+        let index_count = self.values.len() * index_pattern_len;
+        if index_count > view_index_buffer.capacity {
+            view_index_buffer.capacity = index_count.next_power_of_two();
+            view_index_buffer.buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                label: Some("gpu_instances_view_index_buffer"),
+                size: (view_index_buffer.capacity * std::mem::size_of::<u32>()) as u64,
+                usage: BufferUsages::INDEX | BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            }));
+        }
+        match &view_index_buffer.cull_uniform_buffer {
+            Some(cull_uniform_buffer) => {
+                render_queue.write_buffer(cull_uniform_buffer, 0, bytes_of(&uniform));
+            }
+            None => {
+                view_index_buffer.cull_uniform_buffer = Some(
+                    render_device.create_buffer_with_data(&BufferInitDescriptor {
+                        label: Some("gpu_instances_cull_uniform_buffer"),
+                        contents: bytes_of(&uniform),
+                        usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                    }),
+                );
+            }
+        }
+        // NOTE: The culling shader appends to the index count so it is reset every frame
+        match &view_index_buffer.indirect_buffer {
+            Some(indirect_buffer) => {
+                render_queue.write_buffer(
+                    indirect_buffer,
+                    0,
+                    cast_slice(&DRAW_INDEXED_INDIRECT_RESET),
+                );
+            }
+            None => {
+                view_index_buffer.indirect_buffer = Some(render_device.create_buffer_with_data(
+                    &BufferInitDescriptor {
+                        label: Some("gpu_instances_indirect_buffer"),
+                        contents: cast_slice(&DRAW_INDEXED_INDIRECT_RESET),
+                        usage: BufferUsages::COPY_DST
+                            | BufferUsages::INDIRECT
+                            | BufferUsages::STORAGE,
+                    },
+                ));
+            }
+        }
+
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: view_index_buffer
+                    .cull_uniform_buffer
+                    .as_ref()?
+                    .as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: bounds_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: index_pattern_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: view_index_buffer.buffer.as_ref()?.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: view_index_buffer
+                    .indirect_buffer
+                    .as_ref()?
+                    .as_entire_binding(),
+            },
+        ];
+        let layout = match software_raster_target {
+            Some((software_raster_target, _)) => {
+                entries.push(BindGroupEntry {
+                    binding: 5,
+                    resource: software_raster_target.visibility_buffer.as_entire_binding(),
+                });
+                &culling_pipeline.software_raster_layout
+            }
+            None => &culling_pipeline.layout,
+        };
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_instances_cull_bind_group"),
+            layout,
+            entries: &entries,
+        });
+        Some(CullingDispatch {
+            view: view_entity,
+            bind_group,
+            occlusion_bind_group,
+            software_raster: software_raster_target.is_some(),
+            workgroups: (self.values.len() as u32 + CULL_WORKGROUP_SIZE - 1) / CULL_WORKGROUP_SIZE,
+        })
+    }
+
+    /// Uploads the bounds of the given instances for the culling compute shader, starting at
+    /// the given slot.
+    fn write_bounds(
+        &self,
+        render_queue: &RenderQueue,
+        offset: usize,
+        indices: impl Iterator<Item = usize>,
+    ) {
+        if let Some(bounds_buffer) = &self.bounds_buffer {
+            let bounds: Vec<GpuInstanceBounds> = indices
+                .map(|index| GpuInstanceBounds {
+                    center: self.centers[index],
+                    color: self.colors.get(index).copied().unwrap_or(0),
+                    half_extents: self.half_extents[index].extend(0.0),
+                })
+                .collect();
+            render_queue.write_buffer(
+                bounds_buffer,
+                (offset * std::mem::size_of::<GpuInstanceBounds>()) as u64,
+                cast_slice(&bounds),
+            );
+        }
+    }
+
+    /// Uploads instance data to the instance buffer or texture, starting at the given instance.
+    fn write_values(&self, render_queue: &RenderQueue, offset: usize, values: &[S::Gpu]) {
+        if let Some(instance_buffer) = &self.instance_buffer {
+            render_queue.write_buffer(
+                instance_buffer,
+                (offset * std::mem::size_of::<S::Gpu>()) as u64,
+                cast_slice(values),
+            );
+        } else if let Some(instance_texture) = &self.instance_texture {
+            // NOTE: Instances are stored texel after texel, wrapping to the next row, so the
+            // values are written as one copy per row they touch.
+            let bytes: &[u8] = cast_slice(values);
+            let texel_size = std::mem::size_of::<Vec4>();
+            let width = DATA_TEXTURE_WIDTH as usize;
+            let first_texel = offset * texels_per_instance::<S>();
+            let texels = first_texel..first_texel + bytes.len() / texel_size;
+            let mut start = texels.start;
+            while start < texels.end {
+                let end = texels.end.min((start / width + 1) * width);
+                render_queue.write_texture(
+                    ImageCopyTexture {
+                        texture: instance_texture,
+                        mip_level: 0,
+                        origin: Origin3d {
+                            x: (start % width) as u32,
+                            y: (start / width) as u32,
+                            z: 0,
+                        },
+                        aspect: TextureAspect::All,
+                    },
+                    &bytes[(start - first_texel) * texel_size..(end - first_texel) * texel_size],
+                    ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(((end - start) * texel_size) as u32),
+                        rows_per_image: None,
+                    },
+                    Extent3d {
+                        width: (end - start) as u32,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                start = end;
+            }
+        }
+    }
+}
+
+/// Indices of the instances of a set that are visible from one view, in the order of the view.
+#[derive(Default)]
+pub struct ViewIndexBuffer {
+    pub buffer: Option<Buffer>,
+    /// Number of visible indices, unless they are written by the culling shader.
+    pub index_count: u32,
+    /// Arguments of the indirect draw written by the culling shader with
+    /// [`FrustumCulling::Gpu`].
+    pub indirect_buffer: Option<Buffer>,
+    pub cull_uniform_buffer: Option<Buffer>,
+    capacity: usize,
+    /// Instance indices in the order they are drawn for the view when the instances are sorted.
+    order: Vec<u32>,
+    /// Camera position of the view relative to the set when it was last sorted.
+    sort_camera_position: Option<Vec3>,
+}
+
+impl ViewIndexBuffer {
+    /// Sorts the instances by distance to the camera if they have changed or the camera has
+    /// moved far enough since they were last sorted for the view. Returns whether they were
+    /// sorted again.
+    fn sort(
+        &mut self,
+        centers: &[Vec3],
+        camera_position: Vec3,
+        changed: bool,
+        settings: &VertexPullingSettings,
+    ) -> bool {
+        if settings.sorting == InstanceSorting::None {
+            return false;
+        }
+        let camera_moved = self
+            .sort_camera_position
+            .map_or(true, |sort_camera_position| {
+                sort_camera_position.distance(camera_position) > settings.sort_distance_threshold
+            });
+        if !camera_moved && !changed && self.order.len() == centers.len() {
+            return false;
+        }
+        self.sort_camera_position = Some(camera_position);
+
+        let keys: Vec<u32> = centers
+            .iter()
+            .map(|center| {
+                // NOTE: The bits of non-negative floats sort in the same order as their values
+                let key = center.distance_squared(camera_position).to_bits();
+                match settings.sorting {
+                    InstanceSorting::BackToFront => !key,
+                    _ => key,
+                }
+            })
+            .collect();
+        self.order = radix_sort_indices(&keys);
+        true
+    }
+}
+
+/// Number of 16 byte texels that one instance occupies in a data texture.
+#[inline]
+pub fn texels_per_instance<S: PulledShape>() -> usize {
+    std::mem::size_of::<S::Gpu>() / std::mem::size_of::<Vec4>()
+}
+
+/// Number of instances that fit in a data texture of the largest height the adapter supports,
+/// e.g. 2048 texels with WebGL2.
+pub fn max_data_texture_instances<S: PulledShape>(render_device: &RenderDevice) -> usize {
+    let max_height = render_device.limits().max_texture_dimension_2d as usize;
+    max_height * DATA_TEXTURE_WIDTH as usize / texels_per_instance::<S>()
+}
+
+/// GPU storage of all entities with [`Instances<S>`], keyed by entity. The index buffer only
+/// depends on the number of instances so it is shared between all sets and sized for the
+/// largest one. It is not created when drawing with [`IndexMode::Indexless`].
+pub struct GpuInstances<S: PulledShape> {
+    pub index_buffer: Option<Buffer>,
+    /// [`PulledShape::INDEX_PATTERN`] for the culling shader.
+    pub index_pattern_buffer: Option<Buffer>,
+    pub sets: HashMap<Entity, GpuInstanceSet<S>>,
+    index_capacity: usize,
+}
+
+impl<S: PulledShape> Default for GpuInstances<S> {
+    fn default() -> Self {
+        Self {
+            index_buffer: None,
+            index_pattern_buffer: None,
+            sets: HashMap::default(),
+            index_capacity: 0,
+        }
+    }
+}
+
+/// Repeats `index_pattern` for each instance, offsetting it by the instance's first vertex.
+pub fn generate_index_buffer_data(
+    index_pattern: &[u32],
+    vertices_per_instance: u32,
+    num_instances: usize,
+) -> Vec<u32> {
+    let num_indices = num_instances * index_pattern.len();
+
+    (0..num_indices)
+        .map(|i| {
+            let instance = i / index_pattern.len();
+            let instance_local = i % index_pattern.len();
+            instance as u32 * vertices_per_instance + index_pattern[instance_local]
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_instances<S: PulledShape>(
+    extracted_instances: Query<(Entity, &ExtractedInstances<S>)>,
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<VertexPullingPhaseItem>>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<VertexPullingPipeline<S>>,
+    culling_stats: Res<CullingStats<S>>,
+    culling_pipeline: Option<Res<GpuCullingPipeline>>,
+    culling_dispatches: Option<ResMut<CullingDispatches>>,
+    depth_pyramids: Option<Res<DepthPyramids>>,
+    software_raster_targets: Option<Res<SoftwareRasterTargets>>,
+    mut set_variants: ResMut<GpuSetVariants<S>>,
+    gpu_instances: ResMut<GpuInstances<S>>,
+) {
+    let gpu_instances = gpu_instances.into_inner();
+    let frustum_culling = pipeline.settings.frustum_culling;
+
+    if frustum_culling == FrustumCulling::Gpu && gpu_instances.index_pattern_buffer.is_none() {
+        gpu_instances.index_pattern_buffer = Some(render_device.create_buffer_with_data(
+            &BufferInitDescriptor {
+                label: Some("gpu_instances_index_pattern_buffer"),
+                contents: cast_slice(S::INDEX_PATTERN),
+                usage: BufferUsages::STORAGE,
+            },
+        ));
+    }
+    let mut culling_dispatches = culling_dispatches.map(ResMut::into_inner);
+
+    // NOTE: Sets whose entity was despawned or lost its Instances component are no longer
+    // extracted so drop their buffers.
+    gpu_instances
+        .sets
+        .retain(|entity, _| extracted_instances.get(*entity).is_ok());
+
+    let mut max_len = 0;
+    let mut visible = 0;
+    let mut culled = 0;
+    for (entity, extracted) in extracted_instances.iter() {
+        let set = gpu_instances.sets.entry(entity).or_default();
+        let variants_changed = set_variants.changed.contains(&entity);
+        set.update(
+            extracted,
+            set_variants.sets.get(&entity),
+            variants_changed,
+            &render_device,
+            &render_queue,
+            &pipeline,
+        );
+        max_len = max_len.max(set.len());
+
+        if !pipeline.settings.view_index_buffers() {
+            continue;
+        }
+        set.view_index_buffers
+            .retain(|view_entity, _| views.get(*view_entity).is_ok());
+        for (view_entity, view) in views.iter() {
+            match frustum_culling {
+                FrustumCulling::None | FrustumCulling::Cpu => {
+                    let set_visible = set.update_view_indices(
+                        view_entity,
+                        view,
+                        !extracted.changes.is_empty() || variants_changed,
+                        &pipeline.settings,
+                        &render_device,
+                        &render_queue,
+                    );
+                    visible += set_visible;
+                    culled += set.len() - set_visible;
+                }
+                FrustumCulling::Gpu => {
+                    let (culling_pipeline, culling_dispatches) =
+                        match (&culling_pipeline, &mut culling_dispatches) {
+                            (Some(culling_pipeline), Some(culling_dispatches)) => {
+                                (culling_pipeline, culling_dispatches)
+                            }
+                            _ => continue,
+                        };
+                    let depth_pyramid = depth_pyramids
+                        .as_ref()
+                        .filter(|_| pipeline.settings.self_occlusion_culling)
+                        .and_then(|depth_pyramids| depth_pyramids.pyramids.get(&view_entity));
+                    let software_raster_target = software_raster_targets
+                        .as_ref()
+                        .filter(|_| {
+                            pipeline
+                                .settings
+                                .lod
+                                .map_or(false, |lod| lod.software_raster())
+                        })
+                        .and_then(|software_raster_targets| {
+                            software_raster_targets.targets.get(&view_entity)
+                        });
+                    if let Some(dispatch) = set.prepare_gpu_culling(
+                        view_entity,
+                        view,
+                        &render_device,
+                        &render_queue,
+                        culling_pipeline,
+                        gpu_instances.index_pattern_buffer.as_ref().unwrap(),
+                        depth_pyramid,
+                        pipeline.settings.lod.as_ref(),
+                        software_raster_target,
+                    ) {
+                        culling_dispatches.dispatches.push(dispatch);
+                    }
+                }
+            }
+        }
+    }
+    set_variants.changed.clear();
+    if frustum_culling == FrustumCulling::Cpu {
+        culling_stats.set(visible, culled);
+    }
+
+    // NOTE: With an instance vertex buffer every set is drawn as instances of a single copy of
+    // the index pattern.
+    if pipeline.settings.storage == InstanceStorage::VertexBuffer {
+        max_len = max_len.min(1);
+    }
+
+    // NOTE: With frustum culling or sorting each view has its own index buffers, and instances
+    // with variants are drawn without one otherwise.
+    if pipeline.settings.index_mode == IndexMode::IndexBuffer
+        && !pipeline.settings.view_index_buffers()
+        && !S::VARIANTS
+        && max_len > gpu_instances.index_capacity
+    {
+        gpu_instances.index_capacity = max_len.next_power_of_two();
+        let indices = generate_index_buffer_data(
+            S::INDEX_PATTERN,
+            S::VERTICES_PER_INSTANCE,
+            gpu_instances.index_capacity,
+        );
+        gpu_instances.index_buffer = Some(render_device.create_buffer_with_data(
+            &BufferInitDescriptor {
+                label: Some("gpu_instances_index_buffer"),
+                contents: cast_slice(&indices),
+                usage: BufferUsages::INDEX,
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cube, GpuCube};
+
+    fn instances(len: u32) -> Instances<u32> {
+        let mut instances = Instances::new((0..len).collect());
+        assert_eq!(instances.take_dirty(), vec![0..len as usize]);
+        assert!(instances.take_dirty().is_empty());
+        instances
+    }
+
+    #[test]
+    fn overlapping_ranges_are_merged() {
+        let mut instances = instances(10);
+        instances.range_mut(2..5);
+        instances.range_mut(4..8);
+        instances.range_mut(3..4);
+        assert_eq!(instances.take_dirty(), vec![2..8]);
+    }
+
+    #[test]
+    fn adjacent_ranges_are_merged() {
+        let mut instances = instances(10);
+        instances.set(3, 0);
+        instances.set(4, 0);
+        instances.set(2, 0);
+        assert_eq!(instances.take_dirty(), vec![2..5]);
+
+        // NOTE: Only the last range is extended when marking so these are merged when taken
+        instances.set(5, 0);
+        instances.set(1, 0);
+        *instances.get_mut(6).unwrap() = 0;
+        assert_eq!(instances.take_dirty(), vec![1..2, 5..7]);
+    }
+
+    #[test]
+    fn disjoint_ranges_are_kept() {
+        let mut instances = instances(10);
+        instances.set(9, 0);
+        instances.set(1, 0);
+        instances.range_mut(4..6);
+        assert_eq!(instances.take_dirty(), vec![1..2, 4..6, 9..10]);
+    }
+
+    #[test]
+    fn truncate_after_push() {
+        let mut instances = instances(4);
+        instances.push(4);
+        instances.push(5);
+        instances.truncate(5);
+        assert_eq!(instances.take_dirty(), vec![4..5]);
+
+        instances.push(5);
+        instances.truncate(3);
+        assert!(instances.take_dirty().is_empty());
+        assert_eq!(instances.values(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn swap_remove_after_push() {
+        let mut instances = instances(4);
+        instances.push(4);
+        assert_eq!(instances.swap_remove(1), 1);
+        assert_eq!(instances.values(), &[0, 4, 2, 3]);
+        assert_eq!(instances.take_dirty(), vec![1..2]);
+
+        // NOTE: Removing the last instance moves nothing
+        instances.push(5);
+        assert_eq!(instances.swap_remove(4), 5);
+        assert!(instances.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn sorting_without_culling() {
+        let mut set = GpuInstanceSet::<Cube>::default();
+        set.values = vec![GpuCube::default(); 3];
+        set.centers = vec![Vec3::Z, Vec3::ZERO, 2.0 * Vec3::Z];
+        let settings = VertexPullingSettings {
+            sorting: InstanceSorting::BackToFront,
+            ..Default::default()
+        };
+        let camera_position = 10.0 * Vec3::Z;
+        let mut view_index_buffer = ViewIndexBuffer::default();
+        assert!(view_index_buffer.sort(&set.centers, camera_position, true, &settings));
+
+        // NOTE: Without culling the bounds of the instances are not stored
+        assert!(set.half_extents.is_empty());
+        let (indices, visible) =
+            set.view_indices(&view_index_buffer.order, None, None, 1.0, camera_position);
+        assert_eq!(visible, 3);
+        let expected: Vec<u32> = [1, 0, 2]
+            .iter()
+            .flat_map(|index| {
+                let first_vertex = index * Cube::VERTICES_PER_INSTANCE;
+                Cube::INDEX_PATTERN.iter().map(move |i| first_vertex + i)
+            })
+            .collect();
+        assert_eq!(indices, expected);
+    }
+
+    #[test]
+    fn first_vertices() {
+        // NOTE: Variant 1 has no vertices and the set has no variant 3
+        let first_vertices = instance_first_vertices(&[0, 2, 1, 0, 3, 2], &[6, 0, 36]);
+        assert_eq!(first_vertices, vec![0, 6, 42, 42, 48, 48, 84]);
+    }
+
+    #[test]
+    fn first_vertices_without_instances() {
+        assert_eq!(instance_first_vertices(&[], &[6]), vec![0]);
+    }
+}
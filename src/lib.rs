@@ -1,3 +1,383 @@
-pub struct Instances<T> {
-    pub values: Vec<T>,
+//! Vertex pulling for Bevy.
+//!
+//! Instance data is stored in a buffer and the vertex shader indexes into it using an index
+//! calculated from the vertex index. [`VertexPullingPlugin`] contains all of the render graph
+//! glue needed to draw a set of [`Instances`] this way so that new pulled shapes only need to
+//! implement [`PulledShape`]. [`Quad`], [`Cube`], [`Sphere`], [`Cylinder`], [`Capsule`],
+//! [`Line`] and [`Point`] are provided out of the box, as well as [`RotatedCube`] and
+//! [`RotatedQuad`] for rotated instances and [`PackedCube`] for large grids of voxels.
+//! [`MeshInstance`] pulls the vertices of a few small [`Mesh`]es the same way, in a single draw
+//! per set, see [`InstanceMeshes`].
+
+mod culling;
+mod draw;
+mod instances;
+mod lod;
+mod occlusion;
+mod pass;
+mod phase;
+mod pipeline;
+mod shapes;
+mod software_raster;
+mod sort;
+
+pub use culling::*;
+pub use draw::*;
+pub use instances::*;
+pub use lod::*;
+pub use occlusion::*;
+pub use pass::*;
+pub use phase::*;
+pub use pipeline::*;
+pub use shapes::*;
+pub use software_raster::*;
+pub use sort::*;
+
+use std::marker::PhantomData;
+
+use bevy::{
+    core_pipeline::{draw_3d_graph, Transparent3d},
+    prelude::*,
+    render::{
+        render_graph::RenderGraph,
+        render_phase::{sort_phase_system, AddRenderCommand, DrawFunctions},
+        RenderApp, RenderStage,
+    },
+};
+
+pub mod node {
+    pub const VERTEX_PULLING_CULL: &str = "vertex_pulling_cull";
+    pub const VERTEX_PULLING_PASS: &str = "vertex_pulling_pass";
+    pub const VERTEX_PULLING_DEPTH_PYRAMID: &str = "vertex_pulling_depth_pyramid";
+    pub const VERTEX_PULLING_SOFTWARE_RASTER: &str = "vertex_pulling_software_raster";
+}
+
+/// How the vertex shader finds the instance and corner that a vertex belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IndexMode {
+    /// An index buffer repeats [`PulledShape::INDEX_PATTERN`] for every instance. Its size is
+    /// proportional to the number of instances.
+    IndexBuffer,
+    /// No index buffer is used. The vertex shader looks the corner up in its own copy of the
+    /// index pattern using `vertex_index`. The shader is compiled with the `INDEXLESS` shader
+    /// def.
+    Indexless,
+}
+
+impl Default for IndexMode {
+    fn default() -> Self {
+        IndexMode::IndexBuffer
+    }
+}
+
+/// Where the instance data of each set is stored on the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstanceStorage {
+    /// A read-only storage buffer indexed by the vertex shader.
+    StorageBuffer,
+    /// An instance-rate vertex buffer laid out with [`PulledShape::instance_attributes`]. Each
+    /// set is drawn with one instance per value so no storage buffers are needed, e.g. for
+    /// WebGL2. The shader is compiled with the `INSTANCE_BUFFER` shader def.
+    VertexBuffer,
+    /// A texture [`DATA_TEXTURE_WIDTH`] texels wide, in [`PulledShape::DATA_TEXTURE_FORMAT`],
+    /// that the vertex shader reads with `textureLoad`, for adapters without storage buffers in
+    /// the vertex stage.
+    /// [`PulledShape::Gpu`] must be a whole number of texels. The number of instances of a set is
+    /// limited by the maximum texture height, see [`max_data_texture_instances`]. The shader is
+    /// compiled with the `DATA_TEXTURE` shader def.
+    DataTexture,
+}
+
+impl Default for InstanceStorage {
+    fn default() -> Self {
+        InstanceStorage::StorageBuffer
+    }
+}
+
+/// Order in which the instances of a set are drawn. Instances are sorted for each view into the
+/// index buffer of the view, so sorting requires [`IndexMode::IndexBuffer`] and cannot be used
+/// with [`InstanceStorage::VertexBuffer`] or [`FrustumCulling::Gpu`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstanceSorting {
+    /// Instances are drawn in the order they have in [`Instances`].
+    None,
+    /// Instances are sorted nearest first relative to the camera so that early depth testing
+    /// rejects the fragments they hide. Sets are drawn nearest first too.
+    FrontToBack,
+    /// Instances are sorted farthest first and drawn with alpha blending and without depth
+    /// writes. Sets are queued to the [`Transparent3d`](bevy::core_pipeline::Transparent3d)
+    /// phase so that they are ordered with other transparent meshes.
+    BackToFront,
+}
+
+impl Default for InstanceSorting {
+    fn default() -> Self {
+        InstanceSorting::None
+    }
+}
+
+/// How instances outside the view frustum are skipped. Culling requires
+/// [`IndexMode::IndexBuffer`] as the visible instances are drawn from an index buffer per view,
+/// and cannot be used with [`InstanceStorage::VertexBuffer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrustumCulling {
+    /// All instances are drawn.
+    None,
+    /// Instances are tested on the CPU, see [`CullingStats`].
+    Cpu,
+    /// Instances are tested by a compute shader which appends the visible ones to the index
+    /// buffer and writes the arguments of an indirect draw. The order of the visible instances
+    /// is not preserved so it cannot be used with [`InstanceSorting`].
+    Gpu,
+}
+
+impl Default for FrustumCulling {
+    fn default() -> Self {
+        FrustumCulling::None
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VertexPullingSettings {
+    pub index_mode: IndexMode,
+    pub storage: InstanceStorage,
+    /// Draws the instances into the depth buffer first, without a fragment shader, so that the
+    /// color pass only shades the visible fragment of each pixel using
+    /// [`CompareFunction::Equal`](bevy::render::render_resource::CompareFunction::Equal).
+    pub depth_prepass: bool,
+    pub sorting: InstanceSorting,
+    /// Distance the camera of a view has to move relative to a set before its instances are
+    /// sorted again for that view. Sets are always sorted again when their instances change.
+    pub sort_distance_threshold: f32,
+    pub frustum_culling: FrustumCulling,
+    /// Also culls the instances hidden behind the pulled instances drawn in the previous frame,
+    /// using a hierarchical depth pyramid of each view, see [`DepthPyramid`]. Requires
+    /// [`FrustumCulling::Gpu`]. Instances that become visible are drawn one frame late.
+    ///
+    /// The pyramid is not built from the depth of the view, which Bevy does not let us sample,
+    /// but from the visible instances of the sets with this setting, drawn again depth-only. So
+    /// only those sets occlude, e.g. a set of buildings occluding itself. Bevy meshes and sets
+    /// without this setting never occlude anything.
+    pub self_occlusion_culling: bool,
+    /// Draws the instances that are small on screen as impostors, see [`LodSettings`]. The
+    /// geometry of each instance is picked per view when its visible instances are written to
+    /// its index buffer, so this requires frustum culling. Impostors require a shape with
+    /// [`PulledShape::IMPOSTORS`].
+    pub lod: Option<LodSettings>,
+}
+
+impl VertexPullingSettings {
+    /// Whether each view draws the instances of a set from its own index buffer, which holds the
+    /// visible instances in the order of the view.
+    pub fn view_index_buffers(&self) -> bool {
+        self.frustum_culling != FrustumCulling::None || self.sorting != InstanceSorting::None
+    }
+}
+
+/// Labels of the systems added by [`VertexPullingPlugin`], for shapes that prepare resources
+/// before them in [`PulledShape::build`].
+#[derive(Clone, Debug, Hash, PartialEq, Eq, SystemLabel)]
+pub enum VertexPullingSystem {
+    PrepareInstances,
+}
+
+/// Draws every entity with an [`Instances<S>`] component using vertex pulling.
+pub struct VertexPullingPlugin<S: PulledShape> {
+    pub settings: VertexPullingSettings,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S: PulledShape> Default for VertexPullingPlugin<S> {
+    fn default() -> Self {
+        Self::new(VertexPullingSettings::default())
+    }
+}
+
+impl<S: PulledShape> VertexPullingPlugin<S> {
+    pub fn new(settings: VertexPullingSettings) -> Self {
+        Self {
+            settings,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S: PulledShape> Plugin for VertexPullingPlugin<S> {
+    fn build(&self, app: &mut App) {
+        // NOTE: The shaders, phase and pass node are shared by all pulled shapes so they are only
+        // added by the first plugin.
+        if !app
+            .sub_app_mut(RenderApp)
+            .world
+            .contains_resource::<DrawFunctions<VertexPullingPhaseItem>>()
+        {
+            shapes::load_shaders(app);
+
+            let render_app = app.sub_app_mut(RenderApp);
+            render_app
+                .init_resource::<DrawFunctions<VertexPullingPhaseItem>>()
+                .init_resource::<DrawFunctions<VertexPullingPrepassPhaseItem>>()
+                .init_resource::<DrawFunctions<VertexPullingOcclusionPhaseItem>>()
+                .add_system_to_stage(RenderStage::Extract, extract_vertex_pulling_phase)
+                .add_system_to_stage(
+                    RenderStage::PhaseSort,
+                    sort_phase_system::<VertexPullingPhaseItem>,
+                )
+                .add_system_to_stage(
+                    RenderStage::PhaseSort,
+                    sort_phase_system::<VertexPullingPrepassPhaseItem>,
+                )
+                .add_system_to_stage(
+                    RenderStage::PhaseSort,
+                    sort_phase_system::<VertexPullingOcclusionPhaseItem>,
+                );
+
+            let pass_node = VertexPullingPassNode::new(&mut render_app.world);
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+            draw_3d_graph.add_node(node::VERTEX_PULLING_PASS, pass_node);
+            draw_3d_graph
+                .add_node_edge(node::VERTEX_PULLING_PASS, draw_3d_graph::node::MAIN_PASS)
+                .unwrap();
+            draw_3d_graph
+                .add_slot_edge(
+                    draw_3d_graph.input_node().unwrap().id,
+                    draw_3d_graph::input::VIEW_ENTITY,
+                    node::VERTEX_PULLING_PASS,
+                    VertexPullingPassNode::IN_VIEW,
+                )
+                .unwrap();
+        }
+
+        // NOTE: The culling compute pipeline and node are only added when a plugin uses them as
+        // compute shaders are not available on all targets.
+        if self.settings.frustum_culling == FrustumCulling::Gpu
+            && !app
+                .sub_app_mut(RenderApp)
+                .world
+                .contains_resource::<GpuCullingPipeline>()
+        {
+            culling::load_shaders(app);
+
+            let render_app = app.sub_app_mut(RenderApp);
+            render_app
+                .init_resource::<GpuCullingPipeline>()
+                .init_resource::<CullingDispatches>()
+                .add_system_to_stage(RenderStage::Cleanup, clear_culling_dispatches);
+
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+            draw_3d_graph.add_node(node::VERTEX_PULLING_CULL, VertexPullingCullNode);
+            draw_3d_graph
+                .add_node_edge(node::VERTEX_PULLING_CULL, node::VERTEX_PULLING_PASS)
+                .unwrap();
+            draw_3d_graph
+                .add_slot_edge(
+                    draw_3d_graph.input_node().unwrap().id,
+                    draw_3d_graph::input::VIEW_ENTITY,
+                    node::VERTEX_PULLING_CULL,
+                    VertexPullingCullNode::IN_VIEW,
+                )
+                .unwrap();
+        }
+
+        if self.settings.self_occlusion_culling
+            && !app
+                .sub_app_mut(RenderApp)
+                .world
+                .contains_resource::<DepthPyramidPipeline>()
+        {
+            occlusion::load_shaders(app);
+
+            let render_app = app.sub_app_mut(RenderApp);
+            render_app
+                .init_resource::<DepthPyramidPipeline>()
+                .init_resource::<DepthPyramids>()
+                .add_system_to_stage(RenderStage::Prepare, prepare_depth_pyramids)
+                .add_system_to_stage(RenderStage::Cleanup, update_depth_pyramids);
+
+            let depth_pyramid_node = VertexPullingDepthPyramidNode::new(&mut render_app.world);
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+            draw_3d_graph.add_node(node::VERTEX_PULLING_DEPTH_PYRAMID, depth_pyramid_node);
+            draw_3d_graph
+                .add_node_edge(
+                    node::VERTEX_PULLING_PASS,
+                    node::VERTEX_PULLING_DEPTH_PYRAMID,
+                )
+                .unwrap();
+            draw_3d_graph
+                .add_slot_edge(
+                    draw_3d_graph.input_node().unwrap().id,
+                    draw_3d_graph::input::VIEW_ENTITY,
+                    node::VERTEX_PULLING_DEPTH_PYRAMID,
+                    VertexPullingDepthPyramidNode::IN_VIEW,
+                )
+                .unwrap();
+        }
+
+        if self.settings.lod.map_or(false, |lod| lod.software_raster())
+            && !app
+                .sub_app_mut(RenderApp)
+                .world
+                .contains_resource::<SoftwareRasterPipeline>()
+        {
+            software_raster::load_shaders(app);
+
+            let render_app = app.sub_app_mut(RenderApp);
+            render_app
+                .init_resource::<SoftwareRasterPipeline>()
+                .init_resource::<SoftwareRasterTargets>()
+                .add_system_to_stage(RenderStage::Prepare, prepare_software_raster_targets);
+
+            let software_raster_node = VertexPullingSoftwareRasterNode::new(&mut render_app.world);
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+            draw_3d_graph.add_node(node::VERTEX_PULLING_SOFTWARE_RASTER, software_raster_node);
+            draw_3d_graph
+                .add_node_edge(
+                    node::VERTEX_PULLING_PASS,
+                    node::VERTEX_PULLING_SOFTWARE_RASTER,
+                )
+                .unwrap();
+            draw_3d_graph
+                .add_node_edge(
+                    node::VERTEX_PULLING_SOFTWARE_RASTER,
+                    draw_3d_graph::node::MAIN_PASS,
+                )
+                .unwrap();
+            draw_3d_graph
+                .add_slot_edge(
+                    draw_3d_graph.input_node().unwrap().id,
+                    draw_3d_graph::input::VIEW_ENTITY,
+                    node::VERTEX_PULLING_SOFTWARE_RASTER,
+                    VertexPullingSoftwareRasterNode::IN_VIEW,
+                )
+                .unwrap();
+        }
+
+        let culling_stats = CullingStats::<S>::default();
+        app.insert_resource(culling_stats.clone());
+
+        let render_app = app.sub_app_mut(RenderApp);
+        let pipeline =
+            VertexPullingPipeline::<S>::new(&mut render_app.world, self.settings.clone());
+        render_app
+            .insert_resource(pipeline)
+            .add_render_command::<VertexPullingPhaseItem, DrawInstances<S>>()
+            .add_render_command::<VertexPullingPrepassPhaseItem, DrawInstancesPrepass<S>>()
+            .add_render_command::<VertexPullingOcclusionPhaseItem, DrawInstancesOcclusion<S>>()
+            .add_render_command::<Transparent3d, DrawInstances<S>>()
+            .init_resource::<GpuInstances<S>>()
+            .init_resource::<GpuSetVariants<S>>()
+            .insert_resource(culling_stats)
+            .add_system_to_stage(RenderStage::Extract, extract_instances::<S>)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                prepare_instances::<S>.label(VertexPullingSystem::PrepareInstances),
+            )
+            .add_system_to_stage(RenderStage::Queue, queue_instances::<S>);
+
+        S::build(app);
+    }
 }
@@ -0,0 +1,86 @@
+use bevy::{prelude::*, render::view::ExtractedView};
+
+/// Set on the indices of impostor vertices. The instance slot is stored in the bits below
+/// [`LOD_POINT_BIT`], shifted left by 2, and the corner of the impostor in the lowest 2 bits.
+// NOTE: Must match the shaders
+pub const LOD_IMPOSTOR_BIT: u32 = 1 << 31;
+/// Set, together with [`LOD_IMPOSTOR_BIT`], on the indices of point impostor vertices.
+pub const LOD_POINT_BIT: u32 = 1 << 30;
+
+/// Corners of a screen-aligned quad impostor.
+pub const LOD_QUAD_INDEX_PATTERN: [u32; 6] = [0, 1, 2, 2, 1, 3];
+/// Corners of a point impostor, a single triangle that covers one pixel.
+pub const LOD_POINT_INDEX_PATTERN: [u32; 3] = [0, 1, 2];
+
+/// Screen-space sizes below which instances are drawn as impostors instead of their full
+/// geometry, see [`VertexPullingSettings::lod`](crate::VertexPullingSettings::lod).
+///
+/// The size of an instance is the diameter of the sphere around its bounding box, in pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct LodSettings {
+    /// Instances smaller than this are drawn as a screen-aligned quad covering their size.
+    pub quad_size: f32,
+    /// Instances smaller than this are drawn as a point covering a single pixel.
+    pub point_size: f32,
+    /// Instances smaller than this are rasterized by the culling compute shader instead, as
+    /// hardware rasterization shades at least 2x2 pixels per triangle. Requires
+    /// [`FrustumCulling::Gpu`](crate::FrustumCulling::Gpu), see
+    /// [`SoftwareRasterTarget`](crate::SoftwareRasterTarget).
+    pub software_raster_size: f32,
+}
+
+impl LodSettings {
+    /// Whether any instance can be drawn as an impostor.
+    pub fn impostors(&self) -> bool {
+        self.quad_size > 0.0 || self.point_size > 0.0
+    }
+
+    pub fn software_raster(&self) -> bool {
+        self.software_raster_size > 0.0
+    }
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            quad_size: 4.0,
+            point_size: 1.0,
+            software_raster_size: 0.0,
+        }
+    }
+}
+
+/// Geometry an instance is drawn with in a view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LodLevel {
+    Full,
+    Quad,
+    Point,
+}
+
+/// Pixels covered by one unit at a distance of one unit from the camera of a view, assuming a
+/// perspective projection.
+pub fn lod_pixels_per_unit(view: &ExtractedView) -> f32 {
+    0.5 * view.projection.y_axis.y * view.height as f32
+}
+
+/// Picks the geometry of an instance from its size on screen. `camera_position` is relative to
+/// the set of the instance, and the set is assumed to be scaled uniformly.
+#[inline]
+pub fn lod_level(
+    settings: &LodSettings,
+    pixels_per_unit: f32,
+    camera_position: Vec3,
+    center: Vec3,
+    half_extents: Vec3,
+) -> LodLevel {
+    let size = 2.0 * half_extents.length() * pixels_per_unit
+        / camera_position.distance(center).max(f32::EPSILON);
+    if size < settings.point_size {
+        LodLevel::Point
+    } else if size < settings.quad_size {
+        LodLevel::Quad
+    } else {
+        LodLevel::Full
+    }
+}
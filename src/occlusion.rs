@@ -0,0 +1,405 @@
+use std::num::NonZeroU32;
+
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_graph::{self, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_phase::{DrawFunctions, RenderPhase, TrackedRenderPass},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            CachedComputePipelineId, ComputePassDescriptor, ComputePipelineDescriptor, Extent3d,
+            LoadOp, Operations, PipelineCache, RenderPassDepthStencilAttachment,
+            RenderPassDescriptor, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        view::ExtractedView,
+    },
+    utils::HashMap,
+};
+
+use crate::{GpuCullingPipeline, VertexPullingOcclusionPhaseItem};
+
+pub const DEPTH_PYRAMID_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9106553917424750213);
+
+// NOTE: Must match the workgroup_size in depth_pyramid.wgsl
+pub const DEPTH_PYRAMID_WORKGROUP_SIZE: u32 = 8;
+
+pub(crate) fn load_shaders(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        DEPTH_PYRAMID_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("depth_pyramid.wgsl")),
+    );
+}
+
+/// Compute pipelines that reduce the occluder depth of a view into a mip chain where each texel
+/// holds the farthest depth of the texels it covers. The first mip is read from the depth
+/// texture itself, the others from the previous mip.
+pub struct DepthPyramidPipeline {
+    pub first_mip_pipeline_id: CachedComputePipelineId,
+    pub pipeline_id: CachedComputePipelineId,
+    pub first_mip_layout: BindGroupLayout,
+    pub layout: BindGroupLayout,
+}
+
+impl FromWorld for DepthPyramidPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let create_layout = |label, sample_type| {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[
+                    // Input depth
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Output mip
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::StorageTexture {
+                            access: StorageTextureAccess::WriteOnly,
+                            format: TextureFormat::R32Float,
+                            view_dimension: TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        };
+        let first_mip_layout = create_layout(
+            "vertex_pulling_depth_pyramid_first_mip_layout",
+            TextureSampleType::Depth,
+        );
+        let layout = create_layout(
+            "vertex_pulling_depth_pyramid_layout",
+            TextureSampleType::Float { filterable: false },
+        );
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let first_mip_pipeline_id =
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("vertex_pulling_depth_pyramid_first_mip_pipeline".into()),
+                layout: Some(vec![first_mip_layout.clone()]),
+                shader: DEPTH_PYRAMID_SHADER_HANDLE.typed(),
+                shader_defs: vec!["FIRST_MIP".to_string()],
+                entry_point: "downsample_depth".into(),
+            });
+        let pipeline_id = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("vertex_pulling_depth_pyramid_pipeline".into()),
+            layout: Some(vec![layout.clone()]),
+            shader: DEPTH_PYRAMID_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point: "downsample_depth".into(),
+        });
+
+        Self {
+            first_mip_pipeline_id,
+            pipeline_id,
+            first_mip_layout,
+            layout,
+        }
+    }
+}
+
+/// Occluder depth and hierarchical-Z pyramid of a view.
+///
+/// Bevy's depth texture cannot be sampled, so the visible instances of the sets using
+/// [`VertexPullingSettings::self_occlusion_culling`] are drawn again into a depth texture of
+/// their own after the main vertex pulling pass. The pyramid built from it is used to cull the
+/// instances of the next frame, so only those sets occlude.
+///
+/// [`VertexPullingSettings::self_occlusion_culling`]: crate::VertexPullingSettings::self_occlusion_culling
+pub struct DepthPyramid {
+    /// Size of the view, and so of the occluder depth texture.
+    pub size: UVec2,
+    pub depth_texture: Texture,
+    pub depth_view: TextureView,
+    /// R32Float texture whose first mip is half the size of the view.
+    pub pyramid_texture: Texture,
+    pub pyramid_view: TextureView,
+    pub mip_sizes: Vec<UVec2>,
+    pub downsample_bind_groups: Vec<BindGroup>,
+    /// Binds the pyramid to the occlusion variant of the culling pipeline.
+    pub cull_bind_group: BindGroup,
+    /// View projection the pyramid was built with, `None` until it has been built once.
+    pub view_proj: Option<Mat4>,
+    pending_view_proj: Option<Mat4>,
+}
+
+impl DepthPyramid {
+    pub fn new(
+        render_device: &RenderDevice,
+        size: UVec2,
+        pyramid_pipeline: &DepthPyramidPipeline,
+        culling_pipeline: &GpuCullingPipeline,
+    ) -> Self {
+        let depth_texture = render_device.create_texture(&TextureDescriptor {
+            label: Some("vertex_pulling_occluder_depth_texture"),
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        let depth_view = depth_texture.create_view(&TextureViewDescriptor::default());
+
+        let mut mip_sizes = vec![(size / 2).max(UVec2::ONE)];
+        while mip_sizes.last().unwrap().max_element() > 1 {
+            let previous = *mip_sizes.last().unwrap();
+            mip_sizes.push((previous / 2).max(UVec2::ONE));
+        }
+        let pyramid_texture = render_device.create_texture(&TextureDescriptor {
+            label: Some("vertex_pulling_depth_pyramid_texture"),
+            size: Extent3d {
+                width: mip_sizes[0].x,
+                height: mip_sizes[0].y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_sizes.len() as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+        });
+        let pyramid_view = pyramid_texture.create_view(&TextureViewDescriptor::default());
+        let mip_views: Vec<TextureView> = (0..mip_sizes.len() as u32)
+            .map(|mip| {
+                pyramid_texture.create_view(&TextureViewDescriptor {
+                    label: Some("vertex_pulling_depth_pyramid_mip_view"),
+                    base_mip_level: mip,
+                    mip_level_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let downsample_bind_groups = mip_views
+            .iter()
+            .enumerate()
+            .map(|(mip, mip_view)| {
+                let (layout, input) = if mip == 0 {
+                    (&pyramid_pipeline.first_mip_layout, &depth_view)
+                } else {
+                    (&pyramid_pipeline.layout, &mip_views[mip - 1])
+                };
+                render_device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("vertex_pulling_depth_pyramid_bind_group"),
+                    layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(input),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(mip_view),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        let cull_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("vertex_pulling_depth_pyramid_cull_bind_group"),
+            layout: &culling_pipeline.occlusion_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&pyramid_view),
+            }],
+        });
+
+        Self {
+            size,
+            depth_texture,
+            depth_view,
+            pyramid_texture,
+            pyramid_view,
+            mip_sizes,
+            downsample_bind_groups,
+            cull_bind_group,
+            view_proj: None,
+            pending_view_proj: None,
+        }
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.mip_sizes.len() as u32
+    }
+}
+
+/// Depth pyramids of all views, kept from one frame to the next.
+#[derive(Default)]
+pub struct DepthPyramids {
+    pub pyramids: HashMap<Entity, DepthPyramid>,
+}
+
+pub fn prepare_depth_pyramids(
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<VertexPullingOcclusionPhaseItem>>>,
+    render_device: Res<RenderDevice>,
+    pyramid_pipeline: Res<DepthPyramidPipeline>,
+    culling_pipeline: Res<GpuCullingPipeline>,
+    mut depth_pyramids: ResMut<DepthPyramids>,
+) {
+    depth_pyramids
+        .pyramids
+        .retain(|entity, _| views.get(*entity).is_ok());
+    for (entity, view) in views.iter() {
+        let size = UVec2::new(view.width.max(1), view.height.max(1));
+        if depth_pyramids
+            .pyramids
+            .get(&entity)
+            .map_or(true, |depth_pyramid| depth_pyramid.size != size)
+        {
+            depth_pyramids.pyramids.insert(
+                entity,
+                DepthPyramid::new(&render_device, size, &pyramid_pipeline, &culling_pipeline),
+            );
+        }
+        let depth_pyramid = depth_pyramids.pyramids.get_mut(&entity).unwrap();
+        depth_pyramid.pending_view_proj =
+            Some(view.projection * view.transform.compute_matrix().inverse());
+    }
+}
+
+/// Records the view projection of the pyramids built this frame, once the culling of this frame
+/// no longer needs the previous one.
+pub fn update_depth_pyramids(mut depth_pyramids: ResMut<DepthPyramids>) {
+    for depth_pyramid in depth_pyramids.pyramids.values_mut() {
+        if let Some(view_proj) = depth_pyramid.pending_view_proj.take() {
+            depth_pyramid.view_proj = Some(view_proj);
+        }
+    }
+}
+
+/// Draws the occluders of a view into its occluder depth texture and builds its depth pyramid,
+/// after the instances are drawn.
+pub struct VertexPullingDepthPyramidNode {
+    query: QueryState<&'static RenderPhase<VertexPullingOcclusionPhaseItem>, With<ExtractedView>>,
+}
+
+impl VertexPullingDepthPyramidNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl render_graph::Node for VertexPullingDepthPyramidNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(
+            VertexPullingDepthPyramidNode::IN_VIEW,
+            SlotType::Entity,
+        )]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let occlusion_phase = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()), // No window
+        };
+        let depth_pyramid = match world.resource::<DepthPyramids>().pyramids.get(&view_entity) {
+            Some(depth_pyramid) => depth_pyramid,
+            None => return Ok(()),
+        };
+
+        {
+            #[cfg(feature = "trace")]
+            let _vertex_pulling_occluder_pass_span =
+                info_span!("vertex_pulling_occluder_pass").entered();
+            let pass_descriptor = RenderPassDescriptor {
+                label: Some("vertex_pulling_occluder_pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_pyramid.depth_view,
+                    // NOTE: Depth is reversed so the far plane is at 0
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(0.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            };
+
+            let draw_functions = world.resource::<DrawFunctions<VertexPullingOcclusionPhaseItem>>();
+
+            let render_pass = render_context
+                .command_encoder
+                .begin_render_pass(&pass_descriptor);
+            let mut draw_functions = draw_functions.write();
+            let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            for item in &occlusion_phase.items {
+                let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
+                draw_function.draw(world, &mut tracked_pass, view_entity, item);
+            }
+        }
+
+        let pyramid_pipeline = world.resource::<DepthPyramidPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (first_mip_pipeline, pipeline) = match (
+            pipeline_cache.get_compute_pipeline(pyramid_pipeline.first_mip_pipeline_id),
+            pipeline_cache.get_compute_pipeline(pyramid_pipeline.pipeline_id),
+        ) {
+            (Some(first_mip_pipeline), Some(pipeline)) => (first_mip_pipeline, pipeline),
+            _ => return Ok(()),
+        };
+
+        #[cfg(feature = "trace")]
+        let _vertex_pulling_depth_pyramid_span =
+            info_span!("vertex_pulling_depth_pyramid").entered();
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("vertex_pulling_depth_pyramid"),
+            });
+        for (mip, (bind_group, mip_size)) in depth_pyramid
+            .downsample_bind_groups
+            .iter()
+            .zip(&depth_pyramid.mip_sizes)
+            .enumerate()
+        {
+            pass.set_pipeline(if mip == 0 {
+                first_mip_pipeline
+            } else {
+                pipeline
+            });
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch(
+                (mip_size.x + DEPTH_PYRAMID_WORKGROUP_SIZE - 1) / DEPTH_PYRAMID_WORKGROUP_SIZE,
+                (mip_size.y + DEPTH_PYRAMID_WORKGROUP_SIZE - 1) / DEPTH_PYRAMID_WORKGROUP_SIZE,
+                1,
+            );
+        }
+
+        Ok(())
+    }
+}
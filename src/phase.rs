@@ -0,0 +1,195 @@
+use crate::{
+    DrawInstances, DrawInstancesOcclusion, DrawInstancesPrepass, ExtractedInstances, GpuInstances,
+    InstanceSorting, PulledShape, VertexPullingPipeline,
+};
+use bevy::{
+    core_pipeline::Transparent3d,
+    prelude::*,
+    render::{
+        camera::{ActiveCamera, Camera3d},
+        render_phase::{DrawFunctionId, DrawFunctions, EntityPhaseItem, PhaseItem, RenderPhase},
+        view::ExtractedView,
+    },
+};
+
+pub struct VertexPullingPhaseItem {
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+    pub sort_key: u32,
+}
+
+impl PhaseItem for VertexPullingPhaseItem {
+    type SortKey = u32;
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+
+    #[inline]
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for VertexPullingPhaseItem {
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// Depth-only draw of a set of instances, drawn before all [`VertexPullingPhaseItem`]s.
+pub struct VertexPullingPrepassPhaseItem {
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+    pub sort_key: u32,
+}
+
+impl PhaseItem for VertexPullingPrepassPhaseItem {
+    type SortKey = u32;
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+
+    #[inline]
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for VertexPullingPrepassPhaseItem {
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// Depth-only draw of the visible instances of a set into the occluder depth of a
+/// [`DepthPyramid`](crate::DepthPyramid), drawn after all [`VertexPullingPhaseItem`]s.
+pub struct VertexPullingOcclusionPhaseItem {
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+    pub sort_key: u32,
+}
+
+impl PhaseItem for VertexPullingOcclusionPhaseItem {
+    type SortKey = u32;
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+
+    #[inline]
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for VertexPullingOcclusionPhaseItem {
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+pub fn extract_vertex_pulling_phase(
+    mut commands: Commands,
+    active_3d: Res<ActiveCamera<Camera3d>>,
+) {
+    if let Some(entity) = active_3d.get() {
+        commands
+            .get_or_spawn(entity)
+            .insert(RenderPhase::<VertexPullingPhaseItem>::default())
+            .insert(RenderPhase::<VertexPullingPrepassPhaseItem>::default())
+            .insert(RenderPhase::<VertexPullingOcclusionPhaseItem>::default());
+    }
+}
+
+pub fn queue_instances<S: PulledShape>(
+    draw_functions: Res<DrawFunctions<VertexPullingPhaseItem>>,
+    prepass_draw_functions: Res<DrawFunctions<VertexPullingPrepassPhaseItem>>,
+    occlusion_draw_functions: Res<DrawFunctions<VertexPullingOcclusionPhaseItem>>,
+    pipeline: Res<VertexPullingPipeline<S>>,
+    instances_query: Query<(Entity, &ExtractedInstances<S>)>,
+    gpu_instances: Res<GpuInstances<S>>,
+    transparent_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    mut views: Query<(
+        &ExtractedView,
+        &mut RenderPhase<VertexPullingPhaseItem>,
+        &mut RenderPhase<VertexPullingPrepassPhaseItem>,
+        &mut RenderPhase<VertexPullingOcclusionPhaseItem>,
+        &mut RenderPhase<Transparent3d>,
+    )>,
+) {
+    let draw_instances = draw_functions.read().get_id::<DrawInstances<S>>().unwrap();
+    let draw_instances_transparent = transparent_draw_functions
+        .read()
+        .get_id::<DrawInstances<S>>()
+        .unwrap();
+    let draw_instances_prepass = prepass_draw_functions
+        .read()
+        .get_id::<DrawInstancesPrepass<S>>()
+        .unwrap();
+    let draw_instances_occlusion = occlusion_draw_functions
+        .read()
+        .get_id::<DrawInstancesOcclusion<S>>()
+        .unwrap();
+
+    for (entity, extracted) in instances_query.iter() {
+        let has_bind_group = gpu_instances
+            .sets
+            .get(&entity)
+            .map_or(false, |set| set.bind_group.is_some());
+        if !has_bind_group {
+            continue;
+        }
+        let set_position = extracted.transform.w_axis.truncate();
+        for (view, mut phase, mut prepass_phase, mut occlusion_phase, mut transparent_phase) in
+            views.iter_mut()
+        {
+            if pipeline.settings.sorting == InstanceSorting::BackToFront {
+                // NOTE: Transparent3d is sorted by ascending view space z, which increases
+                // towards the camera.
+                let inverse_view_row_2 = view.transform.compute_matrix().inverse().row(2);
+                transparent_phase.add(Transparent3d {
+                    distance: inverse_view_row_2.dot(extracted.transform.w_axis),
+                    pipeline: pipeline.pipeline_id,
+                    entity,
+                    draw_function: draw_instances_transparent,
+                });
+                continue;
+            }
+
+            let sort_key = match pipeline.settings.sorting {
+                // NOTE: The bits of non-negative floats sort in the same order as their values
+                InstanceSorting::FrontToBack => set_position
+                    .distance_squared(view.transform.translation)
+                    .to_bits(),
+                _ => 0,
+            };
+            phase.add(VertexPullingPhaseItem {
+                entity,
+                draw_function: draw_instances,
+                sort_key,
+            });
+            if pipeline.prepass_pipeline_id.is_some() {
+                prepass_phase.add(VertexPullingPrepassPhaseItem {
+                    entity,
+                    draw_function: draw_instances_prepass,
+                    sort_key,
+                });
+            }
+            if pipeline.occlusion_pipeline_id.is_some() {
+                occlusion_phase.add(VertexPullingOcclusionPhaseItem {
+                    entity,
+                    draw_function: draw_instances_occlusion,
+                    sort_key,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::PrimitiveTopology,
+        render_resource::{
+            std140::AsStd140, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+            BindingType, BlendState, BufferBindingType, BufferSize, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
+            FragmentState, FrontFace, MultisampleState, PipelineCache, PolygonMode, PrimitiveState,
+            RenderPipelineDescriptor, ShaderStages, StencilFaceState, StencilState, TextureFormat,
+            TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+        },
+        renderer::RenderDevice,
+        texture::BevyDefault,
+        view::ViewUniform,
+    },
+};
+
+use crate::{
+    FrustumCulling, GpuInstanceSetUniform, IndexMode, InstanceSorting, InstanceStorage,
+    PulledShape, VertexPullingSettings, DATA_TEXTURE_WIDTH,
+};
+
+pub struct VertexPullingPipeline<S> {
+    pub pipeline_id: CachedRenderPipelineId,
+    /// Depth-only pipeline, only created when the depth prepass is enabled.
+    pub prepass_pipeline_id: Option<CachedRenderPipelineId>,
+    /// Depth-only pipeline drawing into the occluder depth of a
+    /// [`DepthPyramid`](crate::DepthPyramid), only created when occlusion culling is enabled.
+    pub occlusion_pipeline_id: Option<CachedRenderPipelineId>,
+    pub instances_layout: BindGroupLayout,
+    pub settings: VertexPullingSettings,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S: PulledShape> VertexPullingPipeline<S> {
+    pub fn new(world: &mut World, settings: VertexPullingSettings) -> Self {
+        assert!(
+            !(settings.depth_prepass && settings.sorting == InstanceSorting::BackToFront),
+            "The depth prepass cannot be used with transparent instances"
+        );
+        assert!(
+            !settings.view_index_buffers()
+                || (settings.index_mode == IndexMode::IndexBuffer
+                    && settings.storage != InstanceStorage::VertexBuffer),
+            "Frustum culling and sorting require IndexMode::IndexBuffer and cannot be used with InstanceStorage::VertexBuffer"
+        );
+        assert!(
+            !(settings.frustum_culling == FrustumCulling::Gpu
+                && settings.sorting != InstanceSorting::None),
+            "GPU frustum culling cannot be used with sorting"
+        );
+        assert!(
+            !settings.self_occlusion_culling || settings.frustum_culling == FrustumCulling::Gpu,
+            "Self-occlusion culling requires FrustumCulling::Gpu"
+        );
+        if let Some(lod) = &settings.lod {
+            assert!(
+                settings.frustum_culling != FrustumCulling::None,
+                "LOD requires frustum culling"
+            );
+            assert!(
+                !lod.impostors() || S::IMPOSTORS,
+                "LOD impostors require a shape that supports them"
+            );
+            assert!(
+                !lod.software_raster() || settings.frustum_culling == FrustumCulling::Gpu,
+                "Software rasterization requires FrustumCulling::Gpu"
+            );
+        }
+
+        if S::VARIANTS {
+            assert!(
+                settings.storage != InstanceStorage::VertexBuffer,
+                "PulledShape::VARIANTS does not support InstanceStorage::VertexBuffer"
+            );
+            assert!(
+                settings.frustum_culling != FrustumCulling::Gpu,
+                "PulledShape::VARIANTS does not support FrustumCulling::Gpu"
+            );
+        }
+
+        let mut shader_defs = S::shader_defs();
+        if settings.index_mode == IndexMode::Indexless {
+            shader_defs.push("INDEXLESS".to_string());
+        }
+        if settings.lod.map_or(false, |lod| lod.impostors()) {
+            shader_defs.push("LOD".to_string());
+        }
+        let mut buffers = Vec::new();
+        if settings.storage == InstanceStorage::VertexBuffer {
+            shader_defs.push("INSTANCE_BUFFER".to_string());
+            let attributes = S::instance_attributes();
+            assert!(
+                !attributes.is_empty(),
+                "InstanceStorage::VertexBuffer requires PulledShape::instance_attributes"
+            );
+            buffers.push(VertexBufferLayout {
+                array_stride: std::mem::size_of::<S::Gpu>() as u64,
+                step_mode: VertexStepMode::Instance,
+                attributes,
+            });
+        }
+        if settings.storage == InstanceStorage::DataTexture {
+            shader_defs.push("DATA_TEXTURE".to_string());
+            assert_eq!(
+                std::mem::size_of::<S::Gpu>() % std::mem::size_of::<Vec4>(),
+                0,
+                "InstanceStorage::DataTexture requires PulledShape::Gpu to be a whole number of texels"
+            );
+            let max_texture_dimension = world
+                .resource::<RenderDevice>()
+                .limits()
+                .max_texture_dimension_2d;
+            assert!(
+                DATA_TEXTURE_WIDTH <= max_texture_dimension,
+                "InstanceStorage::DataTexture requires textures {} texels wide but the adapter supports at most {}",
+                DATA_TEXTURE_WIDTH,
+                max_texture_dimension
+            );
+        }
+
+        let view_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    entries: &[
+                        // View
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: true,
+                                min_binding_size: BufferSize::new(
+                                    ViewUniform::std140_size_static() as u64,
+                                ),
+                            },
+                            count: None,
+                        },
+                    ],
+                    label: Some("shadow_view_layout"),
+                });
+
+        let mut instances_layout_entries = Vec::new();
+        match settings.storage {
+            // Instances
+            InstanceStorage::StorageBuffer => instances_layout_entries.push(BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(0),
+                },
+                count: None,
+            }),
+            InstanceStorage::DataTexture => instances_layout_entries.push(BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Texture {
+                    sample_type: S::DATA_TEXTURE_FORMAT.describe().sample_type,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }),
+            InstanceStorage::VertexBuffer => {}
+        }
+        // Instance set
+        instances_layout_entries.push(BindGroupLayoutEntry {
+            binding: 1,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(
+                    std::mem::size_of::<GpuInstanceSetUniform>() as u64
+                ),
+            },
+            count: None,
+        });
+        if S::VARIANTS {
+            // First vertices
+            instances_layout_entries.push(BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(0),
+                },
+                count: None,
+            });
+            instances_layout_entries.extend(S::variant_layout_entries());
+        }
+        let instances_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("vertex_pulling_instances_layout"),
+                    entries: &instances_layout_entries,
+                });
+
+        let mut descriptor = RenderPipelineDescriptor {
+            label: Some("vertex_pulling_pipeline".into()),
+            layout: Some(vec![view_layout, instances_layout.clone()]),
+            vertex: VertexState {
+                shader: S::shader(),
+                shader_defs: shader_defs.clone(),
+                entry_point: "vertex".into(),
+                buffers,
+            },
+            fragment: Some(FragmentState {
+                shader: S::shader(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState {
+                    front: StencilFaceState::IGNORE,
+                    back: StencilFaceState::IGNORE,
+                    read_mask: 0,
+                    write_mask: 0,
+                },
+                bias: DepthBiasState {
+                    constant: 0,
+                    slope_scale: 0.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState {
+                count: Msaa::default().samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        };
+        if settings.sorting == InstanceSorting::BackToFront {
+            descriptor.fragment.as_mut().unwrap().targets[0].blend =
+                Some(BlendState::ALPHA_BLENDING);
+            descriptor
+                .depth_stencil
+                .as_mut()
+                .unwrap()
+                .depth_write_enabled = false;
+        }
+        S::specialize(&mut descriptor);
+
+        let mut depth_descriptor = descriptor.clone();
+        depth_descriptor.fragment = S::DEPTH_FRAGMENT.then(|| FragmentState {
+            entry_point: "depth_fragment".into(),
+            targets: Vec::new(),
+            ..descriptor.fragment.clone().unwrap()
+        });
+        if !S::DEPTH_FRAGMENT {
+            depth_descriptor.vertex.entry_point = "depth_vertex".into();
+        }
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let prepass_pipeline_id = settings.depth_prepass.then(|| {
+            let mut prepass_descriptor = depth_descriptor.clone();
+            prepass_descriptor.label = Some("vertex_pulling_prepass_pipeline".into());
+            pipeline_cache.queue_render_pipeline(prepass_descriptor)
+        });
+        // NOTE: The occluder depth texture is not multisampled
+        let occlusion_pipeline_id = settings.self_occlusion_culling.then(|| {
+            let mut occlusion_descriptor = depth_descriptor;
+            occlusion_descriptor.label = Some("vertex_pulling_occlusion_pipeline".into());
+            occlusion_descriptor.multisample.count = 1;
+            pipeline_cache.queue_render_pipeline(occlusion_descriptor)
+        });
+        if settings.depth_prepass {
+            // NOTE: The prepass has already written the depth of the nearest fragments so only
+            // those are shaded.
+            let depth_stencil = descriptor.depth_stencil.as_mut().unwrap();
+            depth_stencil.depth_write_enabled = false;
+            depth_stencil.depth_compare = CompareFunction::Equal;
+        }
+        let pipeline_id = pipeline_cache.queue_render_pipeline(descriptor);
+
+        Self {
+            pipeline_id,
+            prepass_pipeline_id,
+            occlusion_pipeline_id,
+            instances_layout,
+            settings,
+            marker: PhantomData,
+        }
+    }
+}
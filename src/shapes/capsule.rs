@@ -0,0 +1,63 @@
+use bevy::{prelude::*, render::render_resource::VertexAttribute};
+
+use crate::{
+    cylinder_instance_attributes, Cube, GpuCylinder, PulledShape, CYLINDERS_SHADER_HANDLE,
+};
+
+/// A cylinder between two points with hemispherical caps, drawn by the shader of [`Cylinder`]
+/// with the `CAPSULE` shader def.
+///
+/// [`Cylinder`]: crate::Cylinder
+#[derive(Clone, Debug, Default)]
+pub struct Capsule {
+    pub color: Color,
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+impl From<&Capsule> for GpuCylinder {
+    fn from(capsule: &Capsule) -> Self {
+        Self {
+            start_radius: capsule.start.extend(capsule.radius),
+            end: capsule.end.extend(1.0),
+            color: capsule.color.as_rgba_f32(),
+        }
+    }
+}
+
+impl PulledShape for Capsule {
+    type Gpu = GpuCylinder;
+
+    const VERTICES_PER_INSTANCE: u32 = Cube::VERTICES_PER_INSTANCE;
+    const INDEX_PATTERN: &'static [u32] = Cube::INDEX_PATTERN;
+    const DEPTH_FRAGMENT: bool = true;
+
+    fn shader() -> Handle<Shader> {
+        CYLINDERS_SHADER_HANDLE.typed()
+    }
+
+    fn shader_defs() -> Vec<String> {
+        vec!["CAPSULE".to_string()]
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuCylinder::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        0.5 * (self.start + self.end)
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        0.5 * (self.end - self.start).abs() + Vec3::splat(self.radius)
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        cylinder_instance_attributes()
+    }
+}
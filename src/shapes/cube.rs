@@ -0,0 +1,118 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::PulledShape;
+
+pub const CUBES_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 17343092250772987267);
+
+/// When enabled, only the three faces of a cube that can face the camera are drawn. The vertex
+/// shader mirrors the cube so that those faces are always the ones in the index pattern.
+pub const CUBE_BACKFACE_OPTIMIZATION: bool = true;
+pub const NUM_CUBE_INDICES: usize = if CUBE_BACKFACE_OPTIMIZATION {
+    3 * 3 * 2
+} else {
+    3 * 6 * 2
+};
+pub const NUM_CUBE_VERTICES: usize = 8;
+
+#[rustfmt::skip]
+const CUBE_INDICES: [u32; 36] = [
+    1, 5, 7, 3, 1, 7,
+    3, 7, 6, 3, 6, 2,
+    5, 4, 6, 7, 5, 6,
+    2, 6, 0, 6, 4, 0,
+    0, 4, 1, 1, 4, 5,
+    1, 3, 2, 1, 2, 0,
+];
+
+const fn cube_index_pattern() -> [u32; NUM_CUBE_INDICES] {
+    let mut indices = [0; NUM_CUBE_INDICES];
+    let mut i = 0;
+    while i < NUM_CUBE_INDICES {
+        indices[i] = CUBE_INDICES[i];
+        i += 1;
+    }
+    indices
+}
+
+/// An axis-aligned cuboid, see [`RotatedCube`](crate::RotatedCube) for rotated ones.
+#[derive(Clone, Debug, Default)]
+pub struct Cube {
+    pub color: Color,
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuCube {
+    pub center: Vec4,
+    pub half_extents: Vec4,
+    pub color: [f32; 4],
+}
+
+impl From<&Cube> for GpuCube {
+    fn from(cube: &Cube) -> Self {
+        Self {
+            center: cube.center.extend(1.0),
+            half_extents: cube.half_extents.extend(0.0),
+            color: cube.color.as_rgba_f32(),
+        }
+    }
+}
+
+impl PulledShape for Cube {
+    type Gpu = GpuCube;
+
+    const VERTICES_PER_INSTANCE: u32 = NUM_CUBE_VERTICES as u32;
+    const INDEX_PATTERN: &'static [u32] = &cube_index_pattern();
+    const IMPOSTORS: bool = true;
+
+    fn shader() -> Handle<Shader> {
+        CUBES_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuCube::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        self.half_extents
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // center
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            // half_extents
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 16,
+                shader_location: 1,
+            },
+            // color
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 32,
+                shader_location: 2,
+            },
+        ]
+    }
+}
@@ -0,0 +1,431 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+struct Cube {
+    center: vec4<f32>;
+    half_extents: vec4<f32>;
+    color: vec4<f32>;
+#ifdef ROTATION
+    rotation: vec4<f32>;
+#endif
+};
+
+#ifdef PACKED_CUBE
+// NOTE: Must match GpuPackedCube
+struct PackedCube {
+    data: vec4<u32>;
+};
+
+struct Cubes {
+    data: array<PackedCube>;
+};
+
+// NOTE: Must match PACKED_CUBE_UNIFORM_SIZE
+let PACKED_CUBE_UNIFORM_SIZE: u32 = 0x80000000u;
+
+fn unpack_cube(data: vec4<u32>) -> Cube {
+    var center: vec3<u32>;
+    var half_extents: vec3<u32>;
+    if ((data.z & PACKED_CUBE_UNIFORM_SIZE) != 0u) {
+        center = vec3<u32>(data.x & 0xffffffu, data.y & 0xffffffu, data.z & 0xffffffu);
+        half_extents = vec3<u32>(data.x >> 24u);
+    } else {
+        center = vec3<u32>(data.x & 0xffffu, data.x >> 16u, data.y & 0xffffu);
+        half_extents = vec3<u32>((data.y >> 16u) & 0xffu, data.y >> 24u, data.z & 0xffu);
+    }
+    return Cube(
+        vec4<f32>(vec3<f32>(center), 1.0),
+        vec4<f32>(0.5 * vec3<f32>(half_extents), 0.0),
+        unpack4x8unorm(data.w)
+    );
+}
+#else
+struct Cubes {
+    data: array<Cube>;
+};
+#endif
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+#ifdef PACKED_CUBE
+    [[location(0)]] data: vec4<u32>;
+#else
+    [[location(0)]] center: vec4<f32>;
+    [[location(1)]] half_extents: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+#ifdef ROTATION
+    [[location(3)]] rotation: vec4<f32>;
+#endif
+#endif
+};
+#else
+#ifdef DATA_TEXTURE
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+#ifdef PACKED_CUBE
+// NOTE: Packed cubes are stored in an RGBA32Uint texture so their words are loaded unchanged
+[[group(1), binding(0)]]
+var cubes: texture_2d<u32>;
+
+fn load_texel(index: u32) -> vec4<u32> {
+    return textureLoad(cubes, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var cubes: texture_2d<f32>;
+
+fn load_texel(index: u32) -> vec4<f32> {
+    return textureLoad(cubes, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#endif
+#else
+[[group(1), binding(0)]]
+var<storage> cubes: Cubes;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef ROTATION
+fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+#endif
+
+#ifdef LOD
+// NOTE: Must match LOD_IMPOSTOR_BIT and LOD_POINT_BIT
+let LOD_IMPOSTOR_BIT: u32 = 0x80000000u;
+let LOD_POINT_BIT: u32 = 0x40000000u;
+#endif
+
+#ifdef INDEXLESS
+// NOTE: Must match Cube::INDEX_PATTERN, the three faces that can face the camera after mirroring
+var<private> cube_indices: array<u32, 18> = array<u32, 18>(
+    1u, 5u, 7u, 3u, 1u, 7u,
+    3u, 7u, 6u, 3u, 6u, 2u,
+    5u, 4u, 6u, 7u, 5u, 6u,
+);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uvw: vec3<f32>;
+    [[location(3)]] color: vec4<f32>;
+    [[location(4)]] raydir: vec3<f32>;
+    [[location(5)]] cube_center: vec3<f32>;
+    [[location(6)]] rayorigin: vec3<f32>;
+    [[location(7)]] half: vec4<f32>;
+#ifdef LOD
+    [[location(8), interpolate(flat)]] impostor: u32;
+#endif
+};
+
+// NOTE: Shared by the color and depth-only entry points so that both compute the clip position
+// with the same expression, which the depth prepass relies on
+fn pull_vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 18u;
+    let corner = cube_indices[vertex_index % 18u];
+#else
+#ifdef LOD
+    let impostor = (vertex_index & LOD_IMPOSTOR_BIT) != 0u;
+    let instance_index = select(
+        vertex_index >> 3u,
+        (vertex_index & ~(LOD_IMPOSTOR_BIT | LOD_POINT_BIT)) >> 2u,
+        impostor
+    );
+    let corner = select(vertex_index & 0x7u, vertex_index & 0x3u, impostor);
+#else
+    let instance_index = vertex_index >> 3u;
+    let corner = vertex_index & 0x7u;
+#endif
+#endif
+#ifdef PACKED_CUBE
+#ifdef INSTANCE_BUFFER
+    let cube = unpack_cube(instance.data);
+#else
+#ifdef DATA_TEXTURE
+    let cube = unpack_cube(load_texel(instance_index));
+#else
+    let cube = unpack_cube(cubes.data[instance_index].data);
+#endif
+#endif
+#else
+#ifdef INSTANCE_BUFFER
+#ifdef ROTATION
+    let cube = Cube(instance.center, instance.half_extents, instance.color, instance.rotation);
+#else
+    let cube = Cube(instance.center, instance.half_extents, instance.color);
+#endif
+#else
+#ifdef DATA_TEXTURE
+#ifdef ROTATION
+    let cube = Cube(
+        load_texel(instance_index * 4u),
+        load_texel(instance_index * 4u + 1u),
+        load_texel(instance_index * 4u + 2u),
+        load_texel(instance_index * 4u + 3u)
+    );
+#else
+    let cube = Cube(
+        load_texel(instance_index * 3u),
+        load_texel(instance_index * 3u + 1u),
+        load_texel(instance_index * 3u + 2u)
+    );
+#endif
+#else
+    let cube = cubes.data[instance_index];
+#endif
+#endif
+#endif
+
+    // branchless mirroring
+    let set_camera_pos = instance_set.inverse_model * vec4<f32>(view.world_position, 1.0);
+    var local_camera_pos = set_camera_pos.xyz - cube.center.xyz;
+#ifdef ROTATION
+    // NOTE: Mirrored in the local frame of the cube so that the three faces facing the camera
+    // are still the ones in the index pattern when it is rotated
+    let inverse_rotation = vec4<f32>(-cube.rotation.xyz, cube.rotation.w);
+    local_camera_pos = quat_rotate(inverse_rotation, local_camera_pos);
+#endif
+    let mirror_mask = u32(local_camera_pos.y < 0.0) << 2u | u32(local_camera_pos.z < 0.0) << 1u | u32(local_camera_pos.x < 0.0);
+    let vx = corner ^ mirror_mask;
+
+    var xyz: vec3<i32> = vec3<i32>(
+        i32(vx & 0x1u),
+        i32((vx & 0x4u) >> 2u),
+        i32((vx & 0x2u) >> 1u)
+    );
+
+    out.uvw = vec3<f32>(xyz);
+    let relative_pos_unit = out.uvw * 2.0 - 1.0;
+    var relative_pos = relative_pos_unit * cube.half_extents.xyz;
+#ifdef ROTATION
+    relative_pos = quat_rotate(cube.rotation, relative_pos);
+#endif
+    out.half = cube.half_extents;
+    // out.world_position = vec4<f32>(cube.center.xyz + relative_pos + view.world_position, 1.0) ;
+    let vpos = instance_set.model * vec4<f32>(cube.center.xyz + relative_pos, 1.0);
+    out.cube_center = (instance_set.model * vec4<f32>(cube.center.xyz, 1.0)).xyz;
+    out.raydir = vpos.xyz - view.world_position;
+
+    out.world_position = vpos;
+    out.world_normal = vec3<f32>(0.0, 0.0, 1.0);
+    out.rayorigin = view.world_position;
+    out.clip_position = view.view_proj * out.world_position;
+    out.color = cube.color;
+#ifdef LOD
+    // NOTE: Impostors are screen-aligned quads around the bounding sphere of the cube, or a
+    // triangle covering a single pixel, at the depth of its center
+    if (impostor) {
+        let center_clip = view.view_proj * vec4<f32>(out.cube_center, 1.0);
+        let world_radius = length((instance_set.model * vec4<f32>(cube.half_extents.xyz, 0.0)).xyz);
+        var half_size = world_radius * vec2<f32>(view.projection[0][0], view.projection[1][1]) / center_clip.w;
+        var offset = vec2<f32>(f32(corner & 0x1u), f32(corner >> 1u)) * 2.0 - 1.0;
+        if ((vertex_index & LOD_POINT_BIT) != 0u) {
+            half_size = 1.0 / vec2<f32>(view.width, view.height);
+            offset = offset * 2.0 + 1.0;
+        }
+        out.clip_position = center_clip + vec4<f32>(offset * half_size * center_clip.w, 0.0, 0.0);
+        out.impostor = 1u;
+    }
+#endif
+    return out;
+}
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+#ifdef INSTANCE_BUFFER
+    return pull_vertex(instance, vertex_index);
+#else
+    return pull_vertex(vertex_index);
+#endif
+}
+
+struct DepthVertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// NOTE: Entry point of the depth-only passes of shapes without PulledShape::DEPTH_FRAGMENT
+[[stage(vertex)]]
+fn depth_vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> DepthVertexOutput {
+#ifdef INSTANCE_BUFFER
+    let out = pull_vertex(instance, vertex_index);
+#else
+    let out = pull_vertex(vertex_index);
+#endif
+    return DepthVertexOutput(out.clip_position);
+}
+//https://www.shadertoy.com/view/ldS3DW
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[builtin(position)]] clip_position: vec4<f32>;
+
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uvw: vec3<f32>;
+    [[location(3)]] color: vec4<f32>;
+    [[location(4)]] raydir: vec3<f32>;
+    [[location(5)]] cube_center: vec3<f32>;
+    [[location(6)]] rayorigin: vec3<f32>;
+    [[location(7)]] half: vec4<f32>;
+#ifdef LOD
+    [[location(8), interpolate(flat)]] impostor: u32;
+#endif
+};
+fn sphIntersect(ro: vec3<f32>, rd: vec3<f32>, sph: vec4<f32>) -> f32 {
+    let oc = ro - sph.xyz;
+    let  b = dot(oc, rd);
+    let c = dot(oc, oc) - sph.w * sph.w;
+    var h = b * b - c;
+    if (h < 0.0) {
+        return -1.0;
+    }
+    h = sqrt(h);
+    return -b - h;
+}
+let ITERATIONS: i32 = 145;
+
+
+
+// void main(void) {
+//     // Screen space position
+//     gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+
+//     // Voxel space position
+//     fPos = Vertex_Position;
+
+//     // World space position
+//     vec3 pos = (Model * vec4(Vertex_Position, 1.0)).xyz;
+    
+//     fEye = pos - (inverse(ViewProj) * vec4(vec3(0), 1)).xyz;
+//     // apply model rotation to ray
+//     fEye = fEye * mat3(Model[0].xyz, Model[1].xyz, Model[2].xyz);
+
+//     v_Position = vec4(Vertex_Position, 1.0);
+// }
+
+// [[stage(vertex)]]
+// fn vertex(vertex: Vertex) -> VertexOutput {
+//     // let world_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+//     let world_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+
+//     var out: VertexOutput;
+//     out.clip_position =  world_position;
+//     out.fpos = vertex.position;
+
+//     let pos = (mesh.model * vec4<f32>(vertex.position, 1.0)).xyz;
+    
+//     let feye = pos - (view * vec4<f32>(.0, .0,.0, 1.)).xyz;
+//     // let feye = pos - (view.view_proj * vec4<f32>(.0, .0,.0, 1.)).xyz;
+//     // apply model rotation to ray
+//     out.feye = feye * mat3x3<f32>(mesh.model[0].xyz, mesh.model[1].xyz, mesh.model[2].xyz);
+
+//     return out;
+// }
+
+
+fn mandelbrot(uvw: vec3<f32>) -> vec4<f32> {
+    let c: vec2<f32> = (vec3<f32>(uvw).xy + vec2<f32>(-0.6, -0.4)) * 4.0 ;
+    var x: f32 = 0.0;
+    var y: f32 = 0.0;
+    var i: i32 = 0;
+
+    for (; i < ITERATIONS; i = i + 1) {
+        if (x * x + y * y > 4.) {
+            break;
+        }
+        let xtemp: f32 = (x * x) - (y * y) + c.x;
+        y = 2. * x * y + c.y;
+        x = xtemp;
+    }
+
+    let frac: f32 = f32(i) / f32(ITERATIONS);
+    let out = vec4<f32>(frac * 1., frac * 1., frac * 3., 0.5);
+    return out;
+}
+fn sph_bad(uvw: vec3<f32>) -> vec2<f32> {
+
+        {
+        let  p = -1.0 + 2.0 * uvw.xy;
+        let r = sqrt(dot(p, p));
+        if (r < 1.0) {
+            let f = (1.0 - sqrt(1.0 - r)) / (r);
+            let x = p.x * f ;
+            let y = p.y * f ;
+            return vec2<f32>(x, y);
+        }
+        return vec2<f32>(0.0, 0.0);
+    }
+}
+
+
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+#ifdef LOD
+    if (in.impostor != 0u) {
+        return in.color;
+    }
+#endif
+    // if ( > 0.7) {
+    //     return vec4<f32>(0.0, 0.0, 1.0, 1.0);
+    // }
+    // let hit = sphIntersect(in.world_position.xyz, normalize(vec3<f32>(0.0,0.0,-1.0)), vec4<f32>(0.0, 0.0, 0.0, 10.0));
+    let hit = sphIntersect(in.rayorigin, normalize(in.raydir), vec4<f32>(in.cube_center, 15.));
+
+
+    let worldPos = normalize(in.raydir) * hit + in.rayorigin;
+
+    // // let pct = distance(in.uvw, vec3<f32>(0.0, 0.0, 0.0));
+
+    // // // return in.color;
+    // return vec4<f32>(hit,hit, hit,  1.0);
+    // var man = mandelbrot(in.uvw);
+    var man = vec4<f32>(in.uvw, 1.0);
+    if (hit < 0.0) {
+        man = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    };
+    return man;
+
+    // return vec4<f32>(sph_bad(in.uvw), 0.0, 1.0);
+}
+
@@ -0,0 +1,106 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{Cube, PulledShape};
+
+pub const CYLINDERS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 11693360218584207817);
+
+/// A capped cylinder between two points, ray-cast in the fragment shader inside the hull of the
+/// box around it, like a [`Sphere`](crate::Sphere).
+#[derive(Clone, Debug, Default)]
+pub struct Cylinder {
+    pub color: Color,
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+/// Instance data of a [`Cylinder`] or a [`Capsule`](crate::Capsule).
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuCylinder {
+    /// Start point in `xyz` and radius in `w`.
+    pub start_radius: Vec4,
+    pub end: Vec4,
+    pub color: [f32; 4],
+}
+
+impl From<&Cylinder> for GpuCylinder {
+    fn from(cylinder: &Cylinder) -> Self {
+        Self {
+            start_radius: cylinder.start.extend(cylinder.radius),
+            end: cylinder.end.extend(1.0),
+            color: cylinder.color.as_rgba_f32(),
+        }
+    }
+}
+
+pub(crate) fn cylinder_instance_attributes() -> Vec<VertexAttribute> {
+    vec![
+        // start_radius
+        VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 0,
+            shader_location: 0,
+        },
+        // end
+        VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 16,
+            shader_location: 1,
+        },
+        // color
+        VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 32,
+            shader_location: 2,
+        },
+    ]
+}
+
+impl PulledShape for Cylinder {
+    type Gpu = GpuCylinder;
+
+    const VERTICES_PER_INSTANCE: u32 = Cube::VERTICES_PER_INSTANCE;
+    const INDEX_PATTERN: &'static [u32] = Cube::INDEX_PATTERN;
+    const DEPTH_FRAGMENT: bool = true;
+
+    fn shader() -> Handle<Shader> {
+        CYLINDERS_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuCylinder::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        0.5 * (self.start + self.end)
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        // NOTE: The caps are discs perpendicular to the axis, which extend less along the axis
+        // directions that are closer to it.
+        let axis = self.end - self.start;
+        let cap_extents = if axis.length_squared() > 0.0 {
+            (Vec3::ONE - (axis * axis) / axis.length_squared())
+                .max(Vec3::ZERO)
+                .sqrt()
+        } else {
+            Vec3::ONE
+        };
+        0.5 * axis.abs() + self.radius * cap_extents
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        cylinder_instance_attributes()
+    }
+}
@@ -0,0 +1,259 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+// NOTE: Must match GpuCylinder
+struct Cylinder {
+    start_radius: vec4<f32>;
+    end: vec4<f32>;
+    color: vec4<f32>;
+};
+
+struct Cylinders {
+    data: array<Cylinder>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+    [[location(0)]] start_radius: vec4<f32>;
+    [[location(1)]] end: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+#else
+#ifdef DATA_TEXTURE
+[[group(1), binding(0)]]
+var cylinders: texture_2d<f32>;
+
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+fn load_texel(index: u32) -> vec4<f32> {
+    return textureLoad(cylinders, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var<storage> cylinders: Cylinders;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef INDEXLESS
+// NOTE: Must match Cube::INDEX_PATTERN, the three faces that can face the camera after mirroring
+var<private> cube_indices: array<u32, 18> = array<u32, 18>(
+    1u, 5u, 7u, 3u, 1u, 7u,
+    3u, 7u, 6u, 3u, 6u, 2u,
+    5u, 4u, 6u, 7u, 5u, 6u,
+);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    // NOTE: The ray is cast in the space of the instance set so that it can be scaled
+    [[location(0)]] set_position: vec3<f32>;
+    [[location(1), interpolate(flat)]] start_radius: vec4<f32>;
+    [[location(2), interpolate(flat)]] end: vec3<f32>;
+    [[location(3), interpolate(flat)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 18u;
+    let corner = cube_indices[vertex_index % 18u];
+#else
+    let instance_index = vertex_index >> 3u;
+    let corner = vertex_index & 0x7u;
+#endif
+#ifdef INSTANCE_BUFFER
+    let cylinder = Cylinder(instance.start_radius, instance.end, instance.color);
+#else
+#ifdef DATA_TEXTURE
+    let cylinder = Cylinder(
+        load_texel(instance_index * 3u),
+        load_texel(instance_index * 3u + 1u),
+        load_texel(instance_index * 3u + 2u)
+    );
+#else
+    let cylinder = cylinders.data[instance_index];
+#endif
+#endif
+
+    // NOTE: The hull is a box around the segment, in a frame with z along its axis
+    let radius = cylinder.start_radius.w;
+    let axis = cylinder.end.xyz - cylinder.start_radius.xyz;
+    let axis_length = length(axis);
+    let z_axis = select(vec3<f32>(0.0, 0.0, 1.0), axis / axis_length, axis_length > 0.0);
+    let x_axis = normalize(cross(z_axis, select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(z_axis.x) > 0.9)));
+    let y_axis = cross(z_axis, x_axis);
+#ifdef CAPSULE
+    let half_extents = vec3<f32>(radius, radius, 0.5 * axis_length + radius);
+#else
+    let half_extents = vec3<f32>(radius, radius, 0.5 * axis_length);
+#endif
+    let center = 0.5 * (cylinder.start_radius.xyz + cylinder.end.xyz);
+
+    // NOTE: Same branchless mirroring of the hull as in cubes.wgsl, in the frame of the hull
+    let set_camera_pos = (instance_set.inverse_model * vec4<f32>(view.world_position, 1.0)).xyz - center;
+    let local_camera_pos = vec3<f32>(dot(set_camera_pos, x_axis), dot(set_camera_pos, y_axis), dot(set_camera_pos, z_axis));
+    let mirror_mask = u32(local_camera_pos.y < 0.0) << 2u | u32(local_camera_pos.z < 0.0) << 1u | u32(local_camera_pos.x < 0.0);
+    let vx = corner ^ mirror_mask;
+
+    let xyz = vec3<f32>(f32(vx & 0x1u), f32((vx & 0x4u) >> 2u), f32((vx & 0x2u) >> 1u));
+    let relative_pos = (xyz * 2.0 - 1.0) * half_extents;
+
+    out.set_position = center + x_axis * relative_pos.x + y_axis * relative_pos.y + z_axis * relative_pos.z;
+    out.clip_position = view.view_proj * instance_set.model * vec4<f32>(out.set_position, 1.0);
+    out.start_radius = cylinder.start_radius;
+    out.end = cylinder.end.xyz;
+    out.color = cylinder.color;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] set_position: vec3<f32>;
+    [[location(1), interpolate(flat)]] start_radius: vec4<f32>;
+    [[location(2), interpolate(flat)]] end: vec3<f32>;
+    [[location(3), interpolate(flat)]] color: vec4<f32>;
+};
+
+struct Hit {
+    set_position: vec3<f32>;
+    set_normal: vec3<f32>;
+    depth: f32;
+};
+
+// https://iquilezles.org/articles/intersectors/
+// NOTE: Discards the fragment if the ray from the camera misses the cylinder or capsule
+fn ray_cast(in: FragmentInput) -> Hit {
+    let ray_origin = (instance_set.inverse_model * vec4<f32>(view.world_position, 1.0)).xyz;
+    let ray_direction = normalize(in.set_position - ray_origin);
+    let radius = in.start_radius.w;
+    let ba = in.end - in.start_radius.xyz;
+    let oa = ray_origin - in.start_radius.xyz;
+    let baba = dot(ba, ba);
+
+    var hit: Hit;
+    var t: f32;
+    if (baba <= 0.0) {
+#ifdef CAPSULE
+        // NOTE: A capsule without an axis is a sphere
+        let b = dot(ray_direction, oa);
+        let c = dot(oa, oa) - radius * radius;
+        let sphere_h = b * b - c;
+        if (sphere_h < 0.0) {
+            discard;
+        }
+        t = -b - sqrt(sphere_h);
+        hit.set_normal = (oa + t * ray_direction) / radius;
+#else
+        // NOTE: A cylinder without an axis has no volume
+        discard;
+#endif
+    } else {
+        let bard = dot(ba, ray_direction);
+        let baoa = dot(ba, oa);
+        let k2 = baba - bard * bard;
+        let k1 = baba * dot(oa, ray_direction) - baoa * bard;
+        let k0 = baba * dot(oa, oa) - baoa * baoa - radius * radius * baba;
+        let h_squared = k1 * k1 - k2 * k0;
+        if (h_squared < 0.0) {
+            discard;
+        }
+        let h = sqrt(h_squared);
+
+        t = (-k1 - h) / k2;
+        let y = baoa + t * bard;
+        if (y > 0.0 && y < baba) {
+            // Body
+            hit.set_normal = (oa + t * ray_direction - ba * y / baba) / radius;
+        } else {
+#ifdef CAPSULE
+            // Hemispherical caps
+            let oc = select(ray_origin - in.end, oa, y <= 0.0);
+            let b = dot(ray_direction, oc);
+            let c = dot(oc, oc) - radius * radius;
+            let cap_h = b * b - c;
+            if (cap_h < 0.0) {
+                discard;
+            }
+            t = -b - sqrt(cap_h);
+            hit.set_normal = (oc + t * ray_direction) / radius;
+#else
+            // Flat caps
+            t = (select(baba, 0.0, y < 0.0) - baoa) / bard;
+            if (abs(k1 + k2 * t) >= h) {
+                discard;
+            }
+            hit.set_normal = ba * sign(y) / sqrt(baba);
+#endif
+        }
+    }
+
+    // NOTE: The hull only has the faces towards the camera, so shapes around the camera are not
+    // drawn
+    if (t < 0.0) {
+        discard;
+    }
+    hit.set_position = ray_origin + ray_direction * t;
+    let clip_position = view.view_proj * instance_set.model * vec4<f32>(hit.set_position, 1.0);
+    // NOTE: Hits closer than the near plane are clamped to it, which is at depth 1 with reversed
+    // depth
+    if (clip_position.w <= view.near) {
+        hit.depth = 1.0;
+    } else {
+        hit.depth = clip_position.z / clip_position.w;
+    }
+    return hit;
+}
+
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> FragmentOutput {
+    let hit = ray_cast(in);
+
+    // NOTE: Normals are transformed by the inverse transpose of the model matrix
+    let world_normal = normalize((vec4<f32>(hit.set_normal, 0.0) * instance_set.inverse_model).xyz);
+    let world_position = (instance_set.model * vec4<f32>(hit.set_position, 1.0)).xyz;
+    // NOTE: Lit by a light at the camera as pulled shapes are unlit
+    let light = max(dot(world_normal, normalize(view.world_position - world_position)), 0.0);
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color.rgb * (0.2 + 0.8 * light), in.color.a);
+    out.depth = hit.depth;
+    return out;
+}
+
+// NOTE: Entry point of the depth-only passes, see PulledShape::DEPTH_FRAGMENT
+[[stage(fragment)]]
+fn depth_fragment(in: FragmentInput) -> [[builtin(frag_depth)]] f32 {
+    return ray_cast(in).depth;
+}
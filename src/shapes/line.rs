@@ -0,0 +1,130 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::PulledShape;
+
+pub const LINES_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 6124590861290384641);
+
+/// A line segment between two points, expanded in the vertex shader to a quad of constant width
+/// on screen.
+///
+/// The width is in pixels so it is unknown to culling and LOD, which only consider the segment.
+#[derive(Clone, Debug, Default)]
+pub struct Line {
+    pub color: Color,
+    pub start: Vec3,
+    pub end: Vec3,
+    /// Width in pixels.
+    pub width: f32,
+    pub caps: LineCaps,
+}
+
+impl Line {
+    /// Segments joining consecutive points. With [`LineCaps::Round`], the caps of consecutive
+    /// segments overlap into round joins.
+    pub fn strip(points: &[Vec3], width: f32, color: Color, caps: LineCaps) -> Vec<Line> {
+        points
+            .windows(2)
+            .map(|points| Line {
+                color,
+                start: points[0],
+                end: points[1],
+                width,
+                caps,
+            })
+            .collect()
+    }
+}
+
+/// How the ends of a [`Line`] are drawn.
+// NOTE: Must match lines.wgsl
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum LineCaps {
+    /// The line ends exactly at its endpoints.
+    Butt = 0,
+    /// The line is extended by a half disc at each endpoint.
+    Round = 1,
+}
+
+impl Default for LineCaps {
+    fn default() -> Self {
+        LineCaps::Butt
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuLine {
+    /// Start point in `xyz` and width in `w`.
+    pub start_width: Vec4,
+    /// End point in `xyz` and [`LineCaps`] in `w`.
+    pub end_caps: Vec4,
+    pub color: [f32; 4],
+}
+
+impl From<&Line> for GpuLine {
+    fn from(line: &Line) -> Self {
+        Self {
+            start_width: line.start.extend(line.width),
+            end_caps: line.end.extend(line.caps as u32 as f32),
+            color: line.color.as_rgba_f32(),
+        }
+    }
+}
+
+impl PulledShape for Line {
+    type Gpu = GpuLine;
+
+    const VERTICES_PER_INSTANCE: u32 = 4;
+    const INDEX_PATTERN: &'static [u32] = &[2, 0, 1, 1, 3, 2];
+    const DEPTH_FRAGMENT: bool = true;
+
+    fn shader() -> Handle<Shader> {
+        LINES_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuLine::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        0.5 * (self.start + self.end)
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        0.5 * (self.end - self.start).abs()
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // start_width
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            // end_caps
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 16,
+                shader_location: 1,
+            },
+            // color
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 32,
+                shader_location: 2,
+            },
+        ]
+    }
+}
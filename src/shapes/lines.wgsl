@@ -0,0 +1,181 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+// NOTE: Must match GpuLine
+struct Line {
+    start_width: vec4<f32>;
+    end_caps: vec4<f32>;
+    color: vec4<f32>;
+};
+
+struct Lines {
+    data: array<Line>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+// NOTE: Must match LineCaps
+let LINE_CAPS_ROUND: u32 = 1u;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+    [[location(0)]] start_width: vec4<f32>;
+    [[location(1)]] end_caps: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+#else
+#ifdef DATA_TEXTURE
+[[group(1), binding(0)]]
+var lines: texture_2d<f32>;
+
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+fn load_texel(index: u32) -> vec4<f32> {
+    return textureLoad(lines, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var<storage> lines: Lines;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef INDEXLESS
+// NOTE: Must match Line::INDEX_PATTERN
+var<private> line_indices: array<u32, 6> = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    // NOTE: Position in pixels along and across the segment, from its start
+    [[location(0), interpolate(linear)]] line_position: vec2<f32>;
+    [[location(1), interpolate(flat)]] segment_length: f32;
+    [[location(2), interpolate(flat)]] half_width: f32;
+    [[location(3), interpolate(flat)]] caps: u32;
+    [[location(4)]] color: vec4<f32>;
+};
+
+// NOTE: Moves a point behind the near plane along the segment onto it, as it cannot be projected.
+// The projection has reversed depth so the near plane is where z equals w.
+fn clip_to_near(clip: vec4<f32>, other: vec4<f32>) -> vec4<f32> {
+    let near_distance = clip.w - clip.z;
+    let other_near_distance = other.w - other.z;
+    if (near_distance >= 0.0 || other_near_distance < 0.0) {
+        return clip;
+    }
+    return mix(clip, other, near_distance / (near_distance - other_near_distance));
+}
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 6u;
+    let corner = line_indices[vertex_index % 6u];
+#else
+    let instance_index = vertex_index >> 2u;
+    let corner = vertex_index & 0x3u;
+#endif
+#ifdef INSTANCE_BUFFER
+    let line = Line(instance.start_width, instance.end_caps, instance.color);
+#else
+#ifdef DATA_TEXTURE
+    let line = Line(
+        load_texel(instance_index * 3u),
+        load_texel(instance_index * 3u + 1u),
+        load_texel(instance_index * 3u + 2u)
+    );
+#else
+    let line = lines.data[instance_index];
+#endif
+#endif
+
+    let start_clip = view.view_proj * instance_set.model * vec4<f32>(line.start_width.xyz, 1.0);
+    let end_clip = view.view_proj * instance_set.model * vec4<f32>(line.end_caps.xyz, 1.0);
+    let start = clip_to_near(start_clip, end_clip);
+    let end = clip_to_near(end_clip, start_clip);
+
+    // NOTE: The quad is expanded in pixels so the width is constant on screen
+    let viewport = vec2<f32>(view.width, view.height);
+    let start_screen = 0.5 * viewport * start.xy / start.w;
+    let end_screen = 0.5 * viewport * end.xy / end.w;
+    let segment_length = length(end_screen - start_screen);
+    let direction = select(vec2<f32>(1.0, 0.0), (end_screen - start_screen) / segment_length, segment_length > 0.0);
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    out.caps = u32(line.end_caps.w);
+    out.half_width = 0.5 * line.start_width.w;
+    let extension = select(0.0, out.half_width, out.caps == LINE_CAPS_ROUND);
+    let is_end = (corner & 0x1u) != 0u;
+    let side = f32(corner >> 1u) * 2.0 - 1.0;
+    let along = select(-extension, segment_length + extension, is_end);
+    let offset = direction * select(-extension, extension, is_end) + normal * side * out.half_width;
+
+    out.clip_position = select(start, end, is_end);
+    // NOTE: Both endpoints behind the near plane collapse the quad so nothing is drawn
+    if (start_clip.w < start_clip.z && end_clip.w < end_clip.z) {
+        out.clip_position = vec4<f32>(0.0);
+    }
+    out.clip_position = vec4<f32>(
+        out.clip_position.xy + offset * 2.0 / viewport * out.clip_position.w,
+        out.clip_position.zw
+    );
+    out.line_position = vec2<f32>(along, side * out.half_width);
+    out.segment_length = segment_length;
+    out.color = line.color;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0), interpolate(linear)]] line_position: vec2<f32>;
+    [[location(1), interpolate(flat)]] segment_length: f32;
+    [[location(2), interpolate(flat)]] half_width: f32;
+    [[location(3), interpolate(flat)]] caps: u32;
+    [[location(4)]] color: vec4<f32>;
+};
+
+// NOTE: Round caps discard the fragments farther than the half width from the segment
+fn clip_caps(in: FragmentInput) {
+    if (in.caps == LINE_CAPS_ROUND) {
+        let nearest = vec2<f32>(clamp(in.line_position.x, 0.0, in.segment_length), 0.0);
+        if (length(in.line_position - nearest) > in.half_width) {
+            discard;
+        }
+    }
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    clip_caps(in);
+    return in.color;
+}
+
+// NOTE: Entry point of the depth-only passes, see PulledShape::DEPTH_FRAGMENT
+[[stage(fragment)]]
+fn depth_fragment(in: FragmentInput) {
+    clip_caps(in);
+}
@@ -0,0 +1,556 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        render_resource::{
+            BindGroupLayoutEntry, BindingType, BufferBindingType, BufferInitDescriptor, BufferSize,
+            BufferUsages, Face, RenderPipelineDescriptor, ShaderStages, TextureFormat,
+        },
+        renderer::RenderDevice,
+        RenderApp, RenderStage,
+    },
+    utils::{HashMap, HashSet},
+};
+use bytemuck::{cast_slice, Pod, Zeroable};
+
+use crate::{GpuSetVariants, PulledShape, SetVariants, VertexPullingSystem};
+
+pub const MESHES_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 2785372408936183925);
+
+/// An instance of one of the meshes of its set, see [`InstanceMeshes`].
+///
+/// The vertices and indices of the meshes of a set are concatenated into storage buffers, with a
+/// table of where each mesh starts, see [`GpuMeshTableEntry`]. The vertex shader finds the
+/// instance, its entry in the table, the index and then the vertex using `vertex_index`, so each
+/// set is drawn with a single non-instanced draw whatever the number of meshes, each instance
+/// with the index count of its own mesh, see [`PulledShape::VARIANTS`]. This suits many
+/// instances of small meshes, for which instanced draws are inefficient. The meshes must be
+/// triangle lists with positions, normals and UVs are optional.
+#[derive(Clone, Debug)]
+pub struct MeshInstance {
+    /// Index of the mesh in [`InstanceMeshes::meshes`].
+    pub mesh: u32,
+    pub color: Color,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    /// Uniform scale of the mesh.
+    pub scale: f32,
+}
+
+impl Default for MeshInstance {
+    fn default() -> Self {
+        Self {
+            mesh: 0,
+            color: Color::WHITE,
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuMeshInstance {
+    /// Translation in `xyz` and scale in `w`.
+    pub translation_scale: Vec4,
+    /// Rotation quaternion as `xyzw`.
+    pub rotation: Vec4,
+    /// Color as `pack4x8unorm`.
+    pub color: u32,
+    pub mesh: u32,
+    pub _padding: [u32; 2],
+}
+
+impl From<&MeshInstance> for GpuMeshInstance {
+    fn from(instance: &MeshInstance) -> Self {
+        Self {
+            translation_scale: instance.translation.extend(instance.scale),
+            rotation: Vec4::from(instance.rotation),
+            color: instance.color.as_rgba_u32(),
+            mesh: instance.mesh,
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl PulledShape for MeshInstance {
+    type Gpu = GpuMeshInstance;
+
+    // NOTE: Unused as each instance draws the index count of its mesh
+    const VERTICES_PER_INSTANCE: u32 = 0;
+    const INDEX_PATTERN: &'static [u32] = &[];
+    const VARIANTS: bool = true;
+    const DATA_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Uint;
+
+    fn shader() -> Handle<Shader> {
+        MESHES_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuMeshInstance::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        self.translation
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        Vec3::splat(self.scale)
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn variant(&self) -> u32 {
+        self.mesh
+    }
+
+    fn variant_layout_entries() -> Vec<BindGroupLayoutEntry> {
+        let storage_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(0),
+            },
+            count: None,
+        };
+        vec![
+            // Mesh vertices
+            storage_entry(3),
+            // Mesh indices
+            storage_entry(4),
+            // Mesh table
+            storage_entry(5),
+        ]
+    }
+
+    fn specialize(descriptor: &mut RenderPipelineDescriptor) {
+        descriptor.primitive.cull_mode = Some(Face::Back);
+    }
+
+    fn build(app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<PulledMeshes>()
+            .init_resource::<ExtractedPulledMeshes>()
+            .add_system_to_stage(RenderStage::Extract, extract_instance_meshes)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                prepare_mesh_tables.before(VertexPullingSystem::PrepareInstances),
+            );
+    }
+}
+
+/// Meshes of a set of [`Instances<MeshInstance>`](crate::Instances), which its instances
+/// refer to by index.
+#[derive(Clone, Component, Debug, Default)]
+pub struct InstanceMeshes {
+    pub meshes: Vec<Handle<Mesh>>,
+}
+
+/// Vertex of a pulled mesh.
+// NOTE: Must match meshes.wgsl
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuMeshVertex {
+    /// Position in `xyz` and the first UV coordinate in `w`.
+    pub position_u: Vec4,
+    /// Normal in `xyz` and the second UV coordinate in `w`.
+    pub normal_v: Vec4,
+}
+
+/// Where a mesh starts in the storage buffers of the [`InstanceMeshes`] of a set.
+// NOTE: Must match meshes.wgsl
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuMeshTableEntry {
+    pub vertex_offset: u32,
+    pub index_offset: u32,
+    pub index_count: u32,
+}
+
+/// Vertices and indices of a [`Mesh`] as they are laid out in the storage buffers.
+#[derive(Clone, Debug, Default)]
+pub struct PulledMeshData {
+    pub vertices: Vec<GpuMeshVertex>,
+    pub indices: Vec<u32>,
+    /// Distance from the origin of the mesh to its farthest vertex.
+    pub radius: f32,
+}
+
+impl PulledMeshData {
+    /// Returns `None` if the mesh is not a triangle list with positions, has no triangles or has
+    /// indices out of the range of its positions. Normals and UVs that do not have one value per
+    /// position are ignored.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+            VertexAttributeValues::Float32x3(positions) => positions,
+            _ => return None,
+        };
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) if normals.len() == positions.len() => {
+                Some(normals)
+            }
+            _ => None,
+        };
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) if uvs.len() == positions.len() => {
+                Some(uvs)
+            }
+            _ => None,
+        };
+
+        // NOTE: Meshes without normals are drawn unlit, see meshes.wgsl
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| {
+                let normal = normals.map_or([0.0; 3], |normals| normals[i]);
+                let uv = uvs.map_or([0.0; 2], |uvs| uvs[i]);
+                GpuMeshVertex {
+                    position_u: Vec3::from(*position).extend(uv[0]),
+                    normal_v: Vec3::from(normal).extend(uv[1]),
+                }
+            })
+            .collect();
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().copied().map(u32::from).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices.len() < 3
+            || indices
+                .iter()
+                .any(|&index| index as usize >= positions.len())
+        {
+            return None;
+        }
+        let radius = positions
+            .iter()
+            .map(|position| Vec3::from(*position).length())
+            .fold(0.0, f32::max);
+
+        Some(Self {
+            vertices,
+            indices,
+            radius,
+        })
+    }
+}
+
+/// The [`InstanceMeshes`] of a set concatenated, as they are laid out in the storage buffers of
+/// its [`SetVariants`].
+#[derive(Clone, Debug, Default)]
+pub struct MeshTableData {
+    pub vertices: Vec<GpuMeshVertex>,
+    pub indices: Vec<u32>,
+    /// A [`GpuMeshTableEntry`] per mesh.
+    pub entries: Vec<GpuMeshTableEntry>,
+    /// [`PulledMeshData::radius`] of each mesh.
+    pub radii: Vec<f32>,
+}
+
+impl MeshTableData {
+    /// Concatenates the meshes. Meshes that are not loaded yet have no indices. Returns `None` if
+    /// none of the meshes has any.
+    pub fn new(
+        meshes: &[Handle<Mesh>],
+        mesh_data: &HashMap<Handle<Mesh>, PulledMeshData>,
+    ) -> Option<Self> {
+        let mut table = Self::default();
+        for mesh in meshes.iter() {
+            let (entry, radius) = match mesh_data.get(mesh) {
+                Some(data) => {
+                    let entry = GpuMeshTableEntry {
+                        vertex_offset: table.vertices.len() as u32,
+                        index_offset: table.indices.len() as u32,
+                        index_count: data.indices.len() as u32,
+                    };
+                    table.vertices.extend_from_slice(&data.vertices);
+                    table.indices.extend_from_slice(&data.indices);
+                    (entry, data.radius)
+                }
+                None => (GpuMeshTableEntry::default(), 0.0),
+            };
+            table.entries.push(entry);
+            table.radii.push(radius);
+        }
+        if table.indices.is_empty() {
+            return None;
+        }
+        Some(table)
+    }
+
+    /// Uploads the vertices, the indices and the table to storage buffers. Each mesh is a variant
+    /// drawn with its index count.
+    pub fn to_set_variants(&self, render_device: &RenderDevice) -> SetVariants {
+        let create_buffer = |label, contents| {
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: BufferUsages::STORAGE,
+            })
+        };
+        SetVariants {
+            vertex_counts: self.entries.iter().map(|entry| entry.index_count).collect(),
+            radii: self.radii.clone(),
+            buffers: vec![
+                create_buffer("mesh_table_vertex_buffer", cast_slice(&self.vertices)),
+                create_buffer("mesh_table_index_buffer", cast_slice(&self.indices)),
+                create_buffer("mesh_table_buffer", cast_slice(&self.entries)),
+            ],
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ExtractedInstanceMeshes {
+    pub meshes: Vec<Handle<Mesh>>,
+}
+
+/// Meshes used by [`InstanceMeshes`] that were loaded, modified or removed since the last frame.
+#[derive(Default)]
+pub struct ExtractedPulledMeshes {
+    pub extracted: Vec<(Handle<Mesh>, PulledMeshData)>,
+    pub removed: Vec<Handle<Mesh>>,
+}
+
+pub fn extract_instance_meshes(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceMeshes)>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut extracted_handles: Local<HashSet<Handle<Mesh>>>,
+) {
+    let mut extracted_meshes = ExtractedPulledMeshes::default();
+    for event in mesh_events.iter() {
+        match event {
+            AssetEvent::Created { .. } => {}
+            AssetEvent::Modified { handle } => {
+                extracted_handles.remove(handle);
+            }
+            AssetEvent::Removed { handle } => {
+                if extracted_handles.remove(handle) {
+                    extracted_meshes.removed.push(handle.clone_weak());
+                }
+            }
+        }
+    }
+
+    for (entity, instance_meshes) in query.iter() {
+        // NOTE: Meshes are extracted once, when they are first used, and again when modified
+        for mesh in instance_meshes.meshes.iter() {
+            if extracted_handles.contains(mesh) {
+                continue;
+            }
+            if let Some(data) = meshes.get(mesh).and_then(PulledMeshData::from_mesh) {
+                extracted_meshes.extracted.push((mesh.clone_weak(), data));
+                extracted_handles.insert(mesh.clone_weak());
+            }
+        }
+
+        commands
+            .get_or_spawn(entity)
+            .insert(ExtractedInstanceMeshes {
+                meshes: instance_meshes
+                    .meshes
+                    .iter()
+                    .map(Handle::clone_weak)
+                    .collect(),
+            });
+    }
+    commands.insert_resource(extracted_meshes);
+}
+
+#[derive(Default)]
+pub struct PulledMeshes {
+    /// CPU copies of the meshes, used to build the mesh tables of the sets.
+    pub mesh_data: HashMap<Handle<Mesh>, PulledMeshData>,
+    /// Meshes of each set when its mesh table was last built.
+    pub sets: HashMap<Entity, Vec<Handle<Mesh>>>,
+}
+
+/// Builds the mesh table of each set of [`MeshInstance`]s as its [`SetVariants`] when its meshes
+/// change.
+pub fn prepare_mesh_tables(
+    extracted_instance_meshes: Query<(Entity, &ExtractedInstanceMeshes)>,
+    extracted_meshes: Res<ExtractedPulledMeshes>,
+    render_device: Res<RenderDevice>,
+    pulled_meshes: ResMut<PulledMeshes>,
+    set_variants: ResMut<GpuSetVariants<MeshInstance>>,
+) {
+    let PulledMeshes { mesh_data, sets } = pulled_meshes.into_inner();
+    let set_variants = set_variants.into_inner();
+
+    for handle in extracted_meshes.removed.iter() {
+        mesh_data.remove(handle);
+    }
+    for (handle, data) in extracted_meshes.extracted.iter() {
+        mesh_data.insert(handle.clone_weak(), data.clone());
+    }
+
+    // NOTE: Sets whose entity was despawned or lost its InstanceMeshes component are no longer
+    // extracted so drop their mesh tables.
+    sets.retain(|entity, _| {
+        let extracted = extracted_instance_meshes.get(*entity).is_ok();
+        if !extracted && set_variants.sets.remove(entity).is_some() {
+            set_variants.changed.insert(*entity);
+        }
+        extracted
+    });
+
+    for (entity, extracted) in extracted_instance_meshes.iter() {
+        // NOTE: The mesh table is rebuilt when one of its meshes is loaded, modified or removed
+        let meshes_changed = extracted.meshes.iter().any(|mesh| {
+            extracted_meshes
+                .extracted
+                .iter()
+                .any(|(handle, _)| handle == mesh)
+                || extracted_meshes.removed.contains(mesh)
+        });
+        if !meshes_changed && sets.get(&entity) == Some(&extracted.meshes) {
+            continue;
+        }
+        sets.insert(entity, extracted.meshes.clone());
+        match MeshTableData::new(&extracted.meshes, mesh_data) {
+            Some(table) => {
+                set_variants
+                    .sets
+                    .insert(entity, table.to_set_variants(&render_device));
+            }
+            None => {
+                set_variants.sets.remove(&entity);
+            }
+        }
+        set_variants.changed.insert(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::HandleId;
+
+    use super::*;
+
+    fn triangle_mesh() -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 1.0, 0.0],
+            ],
+        );
+        mesh
+    }
+
+    #[test]
+    fn indexed_mesh() {
+        let mut mesh = triangle_mesh();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 4]);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+        );
+        mesh.set_indices(Some(Indices::U16(vec![0, 1, 2, 2, 1, 3])));
+
+        let data = PulledMeshData::from_mesh(&mesh).unwrap();
+        assert_eq!(data.indices, vec![0, 1, 2, 2, 1, 3]);
+        assert_eq!(data.vertices.len(), 4);
+        assert_eq!(data.radius, 2.0_f32.sqrt());
+        assert_eq!(data.vertices[3].position_u, Vec4::new(1.0, 1.0, 0.0, 1.0));
+        assert_eq!(data.vertices[3].normal_v, Vec4::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn non_indexed_mesh() {
+        let data = PulledMeshData::from_mesh(&triangle_mesh()).unwrap();
+        assert_eq!(data.indices, vec![0, 1, 2, 3]);
+        assert_eq!(data.vertices.len(), 4);
+    }
+
+    #[test]
+    fn missing_attributes_fall_back() {
+        let data = PulledMeshData::from_mesh(&triangle_mesh()).unwrap();
+        assert_eq!(data.vertices[1].position_u, Vec4::new(1.0, 0.0, 0.0, 0.0));
+        assert_eq!(data.vertices[1].normal_v, Vec4::ZERO);
+    }
+
+    #[test]
+    fn short_attributes_fall_back() {
+        let mut mesh = triangle_mesh();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 2]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[1.0, 1.0]; 3]);
+
+        let data = PulledMeshData::from_mesh(&mesh).unwrap();
+        assert_eq!(data.vertices[0].position_u.w, 0.0);
+        assert_eq!(data.vertices[0].normal_v, Vec4::ZERO);
+    }
+
+    #[test]
+    fn missing_positions() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+        assert!(PulledMeshData::from_mesh(&mesh).is_none());
+    }
+
+    #[test]
+    fn indices_out_of_range() {
+        let mut mesh = triangle_mesh();
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 4])));
+        assert!(PulledMeshData::from_mesh(&mesh).is_none());
+    }
+
+    fn pulled_mesh_data(index_count: usize, vertex_count: usize) -> PulledMeshData {
+        PulledMeshData {
+            vertices: vec![GpuMeshVertex::default(); vertex_count],
+            indices: vec![0; index_count],
+            radius: index_count as f32,
+        }
+    }
+
+    #[test]
+    fn mesh_table_offsets() {
+        let meshes: Vec<Handle<Mesh>> = (0..3)
+            .map(|_| Handle::weak(HandleId::random::<Mesh>()))
+            .collect();
+        let mut mesh_data = HashMap::default();
+        mesh_data.insert(meshes[0].clone(), pulled_mesh_data(6, 4));
+        // NOTE: The second mesh is not loaded yet
+        mesh_data.insert(meshes[2].clone(), pulled_mesh_data(36, 24));
+
+        let table = MeshTableData::new(&meshes, &mesh_data).unwrap();
+        assert_eq!(table.vertices.len(), 28);
+        assert_eq!(table.indices.len(), 42);
+        let entries: Vec<_> = table
+            .entries
+            .iter()
+            .map(|entry| (entry.vertex_offset, entry.index_offset, entry.index_count))
+            .collect();
+        assert_eq!(entries, vec![(0, 0, 6), (0, 0, 0), (4, 6, 36)]);
+        assert_eq!(table.radii, vec![6.0, 0.0, 36.0]);
+    }
+
+    #[test]
+    fn mesh_table_without_loaded_meshes() {
+        let meshes = vec![Handle::weak(HandleId::random::<Mesh>())];
+        assert!(MeshTableData::new(&meshes, &HashMap::default()).is_none());
+    }
+
+    #[test]
+    fn not_a_triangle_list() {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0, 0.0, 0.0]; 4]);
+        assert!(PulledMeshData::from_mesh(&mesh).is_none());
+    }
+}
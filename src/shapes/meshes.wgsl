@@ -0,0 +1,198 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+// NOTE: Must match GpuMeshInstance
+struct MeshInstance {
+    translation_scale: vec4<f32>;
+    rotation: vec4<f32>;
+    color: u32;
+    mesh: u32;
+};
+
+struct MeshInstances {
+    data: array<MeshInstance>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+// NOTE: Must match GpuMeshVertex
+struct MeshVertex {
+    position_u: vec4<f32>;
+    normal_v: vec4<f32>;
+};
+
+struct MeshVertices {
+    data: array<MeshVertex>;
+};
+
+struct MeshIndices {
+    data: array<u32>;
+};
+
+// NOTE: Must match GpuMeshTableEntry
+struct MeshTableEntry {
+    vertex_offset: u32;
+    index_offset: u32;
+    index_count: u32;
+};
+
+struct MeshTable {
+    entries: array<MeshTableEntry>;
+};
+
+// NOTE: Must match instance_first_vertices, followed by the vertex count up to the capacity of
+// the instance set
+struct FirstVertices {
+    data: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+// NOTE: MeshInstance does not support InstanceStorage::VertexBuffer as each instance draws a
+// different number of vertices
+#ifdef DATA_TEXTURE
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+// NOTE: Mesh instances are stored in an RGBA32Uint texture so their words are loaded unchanged
+[[group(1), binding(0)]]
+var instances: texture_2d<u32>;
+
+fn load_texel(index: u32) -> vec4<u32> {
+    return textureLoad(instances, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+
+fn load_instance(index: u32) -> MeshInstance {
+    let translation_scale = load_texel(index * 3u);
+    let rotation = load_texel(index * 3u + 1u);
+    let color_mesh = load_texel(index * 3u + 2u);
+    return MeshInstance(
+        bitcast<vec4<f32>>(translation_scale),
+        bitcast<vec4<f32>>(rotation),
+        color_mesh.x,
+        color_mesh.y
+    );
+}
+#else
+[[group(1), binding(0)]]
+var<storage> instances: MeshInstances;
+
+fn load_instance(index: u32) -> MeshInstance {
+    return instances.data[index];
+}
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+[[group(1), binding(2)]]
+var<storage> first_vertices: FirstVertices;
+
+[[group(1), binding(3)]]
+var<storage> vertices: MeshVertices;
+
+[[group(1), binding(4)]]
+var<storage> indices: MeshIndices;
+
+[[group(1), binding(5)]]
+var<storage> mesh_table: MeshTable;
+
+fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] color: vec4<f32>;
+};
+
+// NOTE: Shared by the color and depth-only entry points so that both compute the clip position
+// with the same expression, which the depth prepass relies on
+fn pull_vertex(vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // NOTE: Binary search for the last instance whose first vertex is not after the vertex, which
+    // skips the instances that draw nothing. The first entry is 0 and the last one, the vertex
+    // count, is after every vertex.
+    var low = 0u;
+    var high = arrayLength(&first_vertices.data) - 1u;
+    loop {
+        if (high - low <= 1u) {
+            break;
+        }
+        let middle = (low + high) / 2u;
+        if (first_vertices.data[middle] <= vertex_index) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let instance = load_instance(low);
+    let local_index = vertex_index - first_vertices.data[low];
+    let entry = mesh_table.entries[instance.mesh];
+    let mesh_vertex = vertices.data[
+        entry.vertex_offset + indices.data[entry.index_offset + local_index]
+    ];
+
+    let position = instance.translation_scale.xyz
+        + quat_rotate(instance.rotation, mesh_vertex.position_u.xyz * instance.translation_scale.w);
+    out.world_position = instance_set.model * vec4<f32>(position, 1.0);
+    // NOTE: Normals are transformed by the inverse transpose of the model matrix
+    let normal = quat_rotate(instance.rotation, mesh_vertex.normal_v.xyz);
+    out.world_normal = (vec4<f32>(normal, 0.0) * instance_set.inverse_model).xyz;
+    out.uv = vec2<f32>(mesh_vertex.position_u.w, mesh_vertex.normal_v.w);
+    out.clip_position = view.view_proj * out.world_position;
+    out.color = unpack4x8unorm(instance.color);
+    return out;
+}
+
+[[stage(vertex)]]
+fn vertex([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    return pull_vertex(vertex_index);
+}
+
+struct DepthVertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// NOTE: Entry point of the depth-only passes of shapes without PulledShape::DEPTH_FRAGMENT
+[[stage(vertex)]]
+fn depth_vertex([[builtin(vertex_index)]] vertex_index: u32) -> DepthVertexOutput {
+    let out = pull_vertex(vertex_index);
+    return DepthVertexOutput(out.clip_position);
+}
+
+struct FragmentInput {
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] color: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    // NOTE: Lit by a light at the camera as pulled shapes are unlit, and meshes without normals
+    // are not lit at all
+    if (all(in.world_normal == vec3<f32>(0.0))) {
+        return in.color;
+    }
+    let to_camera = normalize(view.world_position - in.world_position.xyz);
+    let light = max(dot(normalize(in.world_normal), to_camera), 0.0);
+    return vec4<f32>(in.color.rgb * (0.2 + 0.8 * light), in.color.a);
+}
@@ -0,0 +1,166 @@
+mod capsule;
+mod cube;
+mod cylinder;
+mod line;
+mod mesh;
+mod packed_cube;
+mod point;
+mod quad;
+mod rotated_cube;
+mod rotated_quad;
+mod sphere;
+
+pub use capsule::*;
+pub use cube::*;
+pub use cylinder::*;
+pub use line::*;
+pub use mesh::*;
+pub use packed_cube::*;
+pub use point::*;
+pub use quad::*;
+pub use rotated_cube::*;
+pub use rotated_quad::*;
+pub use sphere::*;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        BindGroupLayoutEntry, RenderPipelineDescriptor, TextureFormat, VertexAttribute,
+    },
+};
+use bytemuck::Pod;
+
+/// A shape whose geometry is generated in the vertex shader from per-instance data.
+pub trait PulledShape: Clone + Send + Sync + 'static {
+    /// Instance data as it is laid out in the instance buffer.
+    type Gpu: Pod + Send + Sync;
+
+    /// Number of vertices each instance is expanded into.
+    const VERTICES_PER_INSTANCE: u32;
+
+    /// Indices of one instance, relative to its first vertex.
+    const INDEX_PATTERN: &'static [u32];
+
+    /// Whether the shader draws impostors when compiled with the `LOD` shader def, for indices
+    /// with [`LOD_IMPOSTOR_BIT`](crate::LOD_IMPOSTOR_BIT) set.
+    const IMPOSTORS: bool = false;
+
+    /// Whether the shader has a `depth_fragment` entry point that the depth-only passes must run,
+    /// e.g. as it discards fragments or writes their depth. Otherwise those passes run its
+    /// `depth_vertex` entry point, which must compute the clip position with the same expression
+    /// as the `vertex` entry point, and have no fragment stage.
+    const DEPTH_FRAGMENT: bool = false;
+
+    /// Whether the instances of a set are drawn with a varying number of vertices, that of one of
+    /// the [`SetVariants`](crate::SetVariants) of their set picked by [`Self::variant`], e.g. one
+    /// of several meshes. [`Self::VERTICES_PER_INSTANCE`] and [`Self::INDEX_PATTERN`] are then
+    /// unused. The shader finds the instance of a vertex with a binary search in the first vertex
+    /// of each instance, see [`instance_first_vertices`](crate::instance_first_vertices), bound
+    /// at group 1, binding 2. Variants cannot be used with
+    /// [`InstanceStorage::VertexBuffer`](crate::InstanceStorage::VertexBuffer) or
+    /// [`FrustumCulling::Gpu`](crate::FrustumCulling::Gpu).
+    const VARIANTS: bool = false;
+
+    /// Format of the texels of the data texture with
+    /// [`InstanceStorage::DataTexture`](crate::InstanceStorage::DataTexture). Shapes whose
+    /// [`Self::Gpu`] holds packed integers use
+    /// [`TextureFormat::Rgba32Uint`] so that their bits are not read as floats.
+    const DATA_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+
+    /// Shader with `vertex` and `fragment` entry points that reads [`Self::Gpu`] from a storage
+    /// buffer bound at group 1, binding 0. The transform of the instance set is bound at group 1,
+    /// binding 1. With the `INSTANCE_BUFFER` shader def, [`Self::Gpu`] is instead passed to the
+    /// vertex entry point as instance-rate vertex attributes. With the `DATA_TEXTURE` shader def,
+    /// it is read from a `texture_2d<f32>` at group 1, binding 0, or a `texture_2d<u32>` with
+    /// [`TextureFormat::Rgba32Uint`] as [`Self::DATA_TEXTURE_FORMAT`].
+    fn shader() -> Handle<Shader>;
+
+    /// Extra shader defs, e.g. to select a variant of a shader shared with other shapes.
+    fn shader_defs() -> Vec<String> {
+        Vec::new()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu;
+
+    /// Position of the instance relative to its set, used to sort instances by distance to the
+    /// camera.
+    fn center(&self) -> Vec3;
+
+    /// Half extents of the axis-aligned box around the instance, used for frustum culling. With
+    /// [`Self::VARIANTS`], they are scaled by the radius of the variant of the instance, see
+    /// [`SetVariants::radii`](crate::SetVariants::radii).
+    fn half_extents(&self) -> Vec3;
+
+    /// Variant of the instance with [`Self::VARIANTS`].
+    fn variant(&self) -> u32 {
+        0
+    }
+
+    /// Flat color of the instance when it is software rasterized, see
+    /// [`LodSettings::software_raster_size`](crate::LodSettings::software_raster_size).
+    fn raster_color(&self) -> Color {
+        Color::WHITE
+    }
+
+    /// Vertex attributes describing [`Self::Gpu`], required by
+    /// [`InstanceStorage::VertexBuffer`](crate::InstanceStorage::VertexBuffer).
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        Vec::new()
+    }
+
+    /// Layout entries of [`SetVariants::buffers`](crate::SetVariants::buffers) with
+    /// [`Self::VARIANTS`], from group 1, binding 3.
+    fn variant_layout_entries() -> Vec<BindGroupLayoutEntry> {
+        Vec::new()
+    }
+
+    /// Customizes the render pipeline, e.g. to change blending or culling.
+    fn specialize(_descriptor: &mut RenderPipelineDescriptor) {}
+
+    /// Adds what the shape needs to the app, once per [`VertexPullingPlugin`], e.g. the systems
+    /// preparing the [`GpuSetVariants`] of its sets.
+    ///
+    /// [`VertexPullingPlugin`]: crate::VertexPullingPlugin
+    /// [`GpuSetVariants`]: crate::GpuSetVariants
+    fn build(_app: &mut App) {}
+}
+
+/// Half extents of the axis-aligned box around a box with the given half extents and rotation.
+pub fn rotated_half_extents(rotation: Quat, half_extents: Vec3) -> Vec3 {
+    let matrix = Mat3::from_quat(rotation);
+    matrix.x_axis.abs() * half_extents.x
+        + matrix.y_axis.abs() * half_extents.y
+        + matrix.z_axis.abs() * half_extents.z
+}
+
+pub(crate) fn load_shaders(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        QUADS_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("quads.wgsl")),
+    );
+    shaders.set_untracked(
+        CUBES_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("cubes.wgsl")),
+    );
+    shaders.set_untracked(
+        CYLINDERS_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("cylinders.wgsl")),
+    );
+    shaders.set_untracked(
+        LINES_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("lines.wgsl")),
+    );
+    shaders.set_untracked(
+        MESHES_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("meshes.wgsl")),
+    );
+    shaders.set_untracked(
+        POINTS_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("points.wgsl")),
+    );
+    shaders.set_untracked(
+        SPHERES_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("spheres.wgsl")),
+    );
+}
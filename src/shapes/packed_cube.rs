@@ -0,0 +1,176 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{TextureFormat, VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{Cube, PulledShape, CUBES_SHADER_HANDLE};
+
+/// Set in [`GpuPackedCube`] when the cube has a [`PackedCubeSize::Uniform`] size, which frees
+/// the bytes of the other half extents for 24-bit positions.
+// NOTE: Must match cubes.wgsl
+pub const PACKED_CUBE_UNIFORM_SIZE: u32 = 1 << 31;
+
+/// A cube on an integer grid, packed into 16 bytes instead of the 48 bytes of a [`GpuCube`].
+/// Packed cubes are always axis-aligned.
+///
+/// Positions are in grid units relative to the origin of the instance set, so the transform of
+/// the set places the chunk in the world and its scale sets the size of a grid cell.
+///
+/// [`GpuCube`]: crate::GpuCube
+#[derive(Clone, Debug, Default)]
+pub struct PackedCube {
+    pub color: Color,
+    /// Center of the cube in grid cells. Each coordinate must fit in 16 bits, or in 24 bits
+    /// with a [`PackedCubeSize::Uniform`] size. Higher bits are dropped.
+    pub center: [u32; 3],
+    pub size: PackedCubeSize,
+}
+
+/// Half extents of a [`PackedCube`] in half grid cells, so 1 fills exactly one cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackedCubeSize {
+    /// The same half extent on all axes, e.g. for voxels. The bytes of the other half extents
+    /// hold the high bits of the center instead, for chunks of up to 2^24 cells per axis.
+    Uniform(u8),
+    /// A half extent per axis, for chunks of up to 2^16 cells per axis.
+    PerAxis([u8; 3]),
+}
+
+impl Default for PackedCubeSize {
+    fn default() -> Self {
+        Self::Uniform(1)
+    }
+}
+
+impl PackedCubeSize {
+    pub fn half_extents(&self) -> [u8; 3] {
+        match *self {
+            Self::Uniform(half_extent) => [half_extent; 3],
+            Self::PerAxis(half_extents) => half_extents,
+        }
+    }
+}
+
+/// Packed instance data decoded by `cubes.wgsl` with the `PACKED_CUBE` shader def. With a
+/// [`PackedCubeSize::PerAxis`] size:
+/// - `data[0]`: center x in the low 16 bits, center y in the high 16 bits
+/// - `data[1]`: center z in the low 16 bits, half extent x and y in the high two bytes
+/// - `data[2]`: half extent z in the low byte, [`PACKED_CUBE_UNIFORM_SIZE`] unset
+/// - `data[3]`: color as `pack4x8unorm`
+///
+/// With a [`PackedCubeSize::Uniform`] size:
+/// - `data[0]`: center x in the low 24 bits, half extent in the high byte
+/// - `data[1]`: center y in the low 24 bits
+/// - `data[2]`: center z in the low 24 bits, [`PACKED_CUBE_UNIFORM_SIZE`] set
+/// - `data[3]`: color as `pack4x8unorm`
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuPackedCube {
+    pub data: [u32; 4],
+}
+
+impl From<&PackedCube> for GpuPackedCube {
+    fn from(cube: &PackedCube) -> Self {
+        let [x, y, z] = cube.center;
+        let color = cube.color.as_rgba_u32();
+        match cube.size {
+            PackedCubeSize::Uniform(half_extent) => {
+                let [x, y, z] = [x, y, z].map(|coordinate| coordinate & 0xff_ffff);
+                Self {
+                    data: [
+                        x | u32::from(half_extent) << 24,
+                        y,
+                        z | PACKED_CUBE_UNIFORM_SIZE,
+                        color,
+                    ],
+                }
+            }
+            PackedCubeSize::PerAxis(half_extents) => {
+                let [x, y, z] = [x, y, z].map(|coordinate| coordinate & 0xffff);
+                let [hx, hy, hz] = half_extents.map(u32::from);
+                Self {
+                    data: [x | y << 16, z | hx << 16 | hy << 24, hz, color],
+                }
+            }
+        }
+    }
+}
+
+impl PulledShape for PackedCube {
+    type Gpu = GpuPackedCube;
+
+    const VERTICES_PER_INSTANCE: u32 = Cube::VERTICES_PER_INSTANCE;
+    const INDEX_PATTERN: &'static [u32] = Cube::INDEX_PATTERN;
+    const IMPOSTORS: bool = true;
+    const DATA_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Uint;
+
+    fn shader() -> Handle<Shader> {
+        CUBES_SHADER_HANDLE.typed()
+    }
+
+    fn shader_defs() -> Vec<String> {
+        vec!["PACKED_CUBE".to_string()]
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuPackedCube::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        Vec3::from(self.center.map(|coordinate| coordinate as f32))
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        0.5 * Vec3::from(self.size.half_extents().map(f32::from))
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // data
+            VertexAttribute {
+                format: VertexFormat::Uint32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_axis_size() {
+        let cube = PackedCube {
+            color: Color::WHITE,
+            center: [1, 2, 0x1_0003],
+            size: PackedCubeSize::PerAxis([4, 5, 6]),
+        };
+        let data = GpuPackedCube::from(&cube).data;
+        assert_eq!(data[0], 1 | 2 << 16);
+        // NOTE: The bits past 16 of the center are dropped
+        assert_eq!(data[1], 3 | 4 << 16 | 5 << 24);
+        assert_eq!(data[2], 6);
+        assert_eq!(data[3], u32::MAX);
+    }
+
+    #[test]
+    fn uniform_size_has_wider_positions() {
+        let cube = PackedCube {
+            color: Color::WHITE,
+            center: [0xab_cdef, 0x12_3456, 0xff_ffff],
+            size: PackedCubeSize::Uniform(7),
+        };
+        let data = GpuPackedCube::from(&cube).data;
+        assert_eq!(data[0], 0xab_cdef | 7 << 24);
+        assert_eq!(data[1], 0x12_3456);
+        assert_eq!(data[2], 0xff_ffff | PACKED_CUBE_UNIFORM_SIZE);
+        assert_eq!(cube.half_extents(), Vec3::splat(3.5));
+    }
+}
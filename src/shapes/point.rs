@@ -0,0 +1,127 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{TextureFormat, VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::PulledShape;
+
+pub const POINTS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 15381045622468094357);
+
+/// Largest [`Point::size`] that can be packed into a [`GpuPoint`].
+pub const POINT_MAX_SIZE: u8 = 0x3f;
+/// Set in [`GpuPoint`] for [`PointFootprint::Round`].
+// NOTE: Must match points.wgsl
+pub const POINT_ROUND: u32 = 1 << 30;
+/// Set in [`GpuPoint`] when [`Point::attenuation`] is enabled.
+pub const POINT_ATTENUATION: u32 = 1 << 31;
+/// Distance to the camera at which attenuated points are [`Point::size`] pixels.
+pub const POINT_ATTENUATION_DISTANCE: f32 = 10.0;
+
+/// A point drawn as a screen-aligned square or disc of a fixed size in pixels, e.g. for point
+/// clouds.
+///
+/// The size is in pixels so it is unknown to culling and LOD, which only consider the position.
+#[derive(Clone, Debug, Default)]
+pub struct Point {
+    /// Color of the point, without alpha.
+    pub color: Color,
+    pub position: Vec3,
+    /// Size in pixels, at most [`POINT_MAX_SIZE`].
+    pub size: u8,
+    pub footprint: PointFootprint,
+    /// Whether the point shrinks with the distance to the camera, down to a single pixel, so that
+    /// it is [`Self::size`] pixels at [`POINT_ATTENUATION_DISTANCE`].
+    pub attenuation: bool,
+}
+
+/// Shape of a [`Point`] on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PointFootprint {
+    Square,
+    /// Fragments outside the disc inscribed in the square are discarded.
+    Round,
+}
+
+impl Default for PointFootprint {
+    fn default() -> Self {
+        PointFootprint::Square
+    }
+}
+
+/// Point packed into 16 bytes so that very large point clouds fit in memory:
+/// - `position`: position relative to the instance set
+/// - `data`: color as RGB8 in the low 3 bytes, size in the low 6 bits of the high byte, and the
+///   [`POINT_ROUND`] and [`POINT_ATTENUATION`] flags in its high 2 bits
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuPoint {
+    pub position: Vec3,
+    pub data: u32,
+}
+
+impl From<&Point> for GpuPoint {
+    fn from(point: &Point) -> Self {
+        let mut flags = 0;
+        if point.footprint == PointFootprint::Round {
+            flags |= POINT_ROUND;
+        }
+        if point.attenuation {
+            flags |= POINT_ATTENUATION;
+        }
+        Self {
+            position: point.position,
+            data: (point.color.as_rgba_u32() & 0x00ff_ffff)
+                | u32::from(point.size.min(POINT_MAX_SIZE)) << 24
+                | flags,
+        }
+    }
+}
+
+impl PulledShape for Point {
+    type Gpu = GpuPoint;
+
+    const VERTICES_PER_INSTANCE: u32 = 4;
+    const INDEX_PATTERN: &'static [u32] = &[2, 0, 1, 1, 3, 2];
+    const DEPTH_FRAGMENT: bool = true;
+    const DATA_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Uint;
+
+    fn shader() -> Handle<Shader> {
+        POINTS_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuPoint::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        self.position
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        Vec3::ZERO
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // position
+            VertexAttribute {
+                format: VertexFormat::Float32x3,
+                offset: 0,
+                shader_location: 0,
+            },
+            // data
+            VertexAttribute {
+                format: VertexFormat::Uint32,
+                offset: 12,
+                shader_location: 1,
+            },
+        ]
+    }
+}
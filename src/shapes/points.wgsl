@@ -0,0 +1,140 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+// NOTE: Must match GpuPoint
+struct Point {
+    position: vec3<f32>;
+    data: u32;
+};
+
+struct Points {
+    data: array<Point>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+// NOTE: Must match POINT_ROUND, POINT_ATTENUATION and POINT_ATTENUATION_DISTANCE
+let POINT_ROUND: u32 = 0x40000000u;
+let POINT_ATTENUATION: u32 = 0x80000000u;
+let POINT_ATTENUATION_DISTANCE: f32 = 10.0;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] data: u32;
+};
+#else
+#ifdef DATA_TEXTURE
+// NOTE: Points are stored in an RGBA32Uint texture so their packed word is loaded unchanged
+[[group(1), binding(0)]]
+var points: texture_2d<u32>;
+
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+fn load_texel(index: u32) -> vec4<u32> {
+    return textureLoad(points, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var<storage> points: Points;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef INDEXLESS
+// NOTE: Must match Point::INDEX_PATTERN
+var<private> point_indices: array<u32, 6> = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    // NOTE: Position in the footprint of the point, from -1 to 1
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] color: vec4<f32>;
+    [[location(2), interpolate(flat)]] is_round: u32;
+};
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 6u;
+    let corner = point_indices[vertex_index % 6u];
+#else
+    let instance_index = vertex_index >> 2u;
+    let corner = vertex_index & 0x3u;
+#endif
+#ifdef INSTANCE_BUFFER
+    let point = Point(instance.position, instance.data);
+#else
+#ifdef DATA_TEXTURE
+    let texel = load_texel(instance_index);
+    let point = Point(bitcast<vec3<f32>>(texel.xyz), texel.w);
+#else
+    let point = points.data[instance_index];
+#endif
+#endif
+
+    let center_clip = view.view_proj * instance_set.model * vec4<f32>(point.position, 1.0);
+    var size = f32((point.data >> 24u) & 0x3fu);
+    if ((point.data & POINT_ATTENUATION) != 0u) {
+        size = max(size * POINT_ATTENUATION_DISTANCE / center_clip.w, 1.0);
+    }
+
+    // NOTE: The quad is expanded in pixels so its size is fixed on screen
+    out.uv = vec2<f32>(f32(corner & 0x1u), f32(corner >> 1u)) * 2.0 - 1.0;
+    let offset = out.uv * size / vec2<f32>(view.width, view.height);
+    out.clip_position = vec4<f32>(center_clip.xy + offset * center_clip.w, center_clip.zw);
+    out.color = vec4<f32>(unpack4x8unorm(point.data).rgb, 1.0);
+    out.is_round = u32((point.data & POINT_ROUND) != 0u);
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] color: vec4<f32>;
+    [[location(2), interpolate(flat)]] is_round: u32;
+};
+
+// NOTE: Round points discard the fragments outside the disc inscribed in their quad
+fn clip_footprint(in: FragmentInput) {
+    if (in.is_round != 0u && dot(in.uv, in.uv) > 1.0) {
+        discard;
+    }
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    clip_footprint(in);
+    return in.color;
+}
+
+// NOTE: Entry point of the depth-only passes, see PulledShape::DEPTH_FRAGMENT
+[[stage(fragment)]]
+fn depth_fragment(in: FragmentInput) {
+    clip_footprint(in);
+}
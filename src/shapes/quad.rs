@@ -0,0 +1,129 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{Face, RenderPipelineDescriptor, VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::PulledShape;
+
+pub const QUADS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7659167879172469997);
+
+/// A quad in the XY plane of its instance set, or facing the camera, see [`Billboard`]. See
+/// [`RotatedQuad`](crate::RotatedQuad) for quads in other planes.
+#[derive(Clone, Debug, Default)]
+pub struct Quad {
+    pub color: Color,
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub billboard: Billboard,
+}
+
+/// How a [`Quad`] is oriented relative to the camera.
+// NOTE: Must match quads.wgsl
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Billboard {
+    /// The quad stays in the XY plane of its instance set.
+    None = 0,
+    /// The quad faces the camera position, rotating freely around its center.
+    Spherical = 1,
+    /// The quad faces the camera position while rotating only around the Y axis of its instance
+    /// set, as for trees or sprites standing on the ground.
+    Cylindrical = 2,
+    /// The quad is parallel to the screen and `half_extents.xy` are in pixels, so it keeps the
+    /// same size on screen at any distance.
+    ///
+    /// Culling and LOD bound such quads by their largest half extent on all axes, taken as world
+    /// units, since their size in the world depends on the view.
+    ScreenAligned = 3,
+}
+
+impl Default for Billboard {
+    fn default() -> Self {
+        Billboard::None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuQuad {
+    pub center: Vec4,
+    pub half_extents: Vec4,
+    pub color: [f32; 4],
+}
+
+impl From<&Quad> for GpuQuad {
+    fn from(quad: &Quad) -> Self {
+        Self {
+            center: quad.center.extend(1.0),
+            // NOTE: The billboard mode is stored in the otherwise unused w component
+            half_extents: quad.half_extents.extend(quad.billboard as u32 as f32),
+            color: quad.color.as_rgba_f32(),
+        }
+    }
+}
+
+impl PulledShape for Quad {
+    type Gpu = GpuQuad;
+
+    const VERTICES_PER_INSTANCE: u32 = 4;
+    const INDEX_PATTERN: &'static [u32] = &[2, 0, 1, 1, 3, 2];
+
+    fn shader() -> Handle<Shader> {
+        QUADS_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuQuad::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        match self.billboard {
+            Billboard::None => self.half_extents,
+            Billboard::Spherical => Vec3::splat(self.half_extents.truncate().length()),
+            Billboard::Cylindrical => Vec3::new(
+                self.half_extents.x,
+                self.half_extents.y,
+                self.half_extents.x,
+            ),
+            Billboard::ScreenAligned => Vec3::splat(self.half_extents.x.max(self.half_extents.y)),
+        }
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // center
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            // half_extents
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 16,
+                shader_location: 1,
+            },
+            // color
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 32,
+                shader_location: 2,
+            },
+        ]
+    }
+
+    fn specialize(descriptor: &mut RenderPipelineDescriptor) {
+        descriptor.primitive.cull_mode = Some(Face::Back);
+    }
+}
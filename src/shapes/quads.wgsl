@@ -0,0 +1,239 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+struct Quad {
+    center: vec4<f32>;
+    half_extents: vec4<f32>;
+    color: vec4<f32>;
+#ifdef ROTATION
+    rotation: vec4<f32>;
+#endif
+};
+
+struct Quads {
+    data: array<Quad>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+// NOTE: Must match Billboard
+let BILLBOARD_NONE: u32 = 0u;
+let BILLBOARD_CYLINDRICAL: u32 = 2u;
+let BILLBOARD_SCREEN_ALIGNED: u32 = 3u;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+    [[location(0)]] center: vec4<f32>;
+    [[location(1)]] half_extents: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+#ifdef ROTATION
+    [[location(3)]] rotation: vec4<f32>;
+#endif
+};
+#else
+#ifdef DATA_TEXTURE
+[[group(1), binding(0)]]
+var quads: texture_2d<f32>;
+
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+fn load_texel(index: u32) -> vec4<f32> {
+    return textureLoad(quads, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var<storage> quads: Quads;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef ROTATION
+fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+#endif
+
+#ifdef INDEXLESS
+// NOTE: Must match Quad::INDEX_PATTERN
+var<private> quad_indices: array<u32, 6> = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] color: vec4<f32>;
+};
+
+// NOTE: Shared by the color and depth-only entry points so that both compute the clip position
+// with the same expression, which the depth prepass relies on
+fn pull_vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 6u;
+    let corner = quad_indices[vertex_index % 6u];
+#else
+    let instance_index = vertex_index >> 2u;
+    let corner = vertex_index & 0x3u;
+#endif
+#ifdef INSTANCE_BUFFER
+#ifdef ROTATION
+    let quad = Quad(instance.center, instance.half_extents, instance.color, instance.rotation);
+#else
+    let quad = Quad(instance.center, instance.half_extents, instance.color);
+#endif
+#else
+#ifdef DATA_TEXTURE
+#ifdef ROTATION
+    let quad = Quad(
+        load_texel(instance_index * 4u),
+        load_texel(instance_index * 4u + 1u),
+        load_texel(instance_index * 4u + 2u),
+        load_texel(instance_index * 4u + 3u)
+    );
+#else
+    let quad = Quad(
+        load_texel(instance_index * 3u),
+        load_texel(instance_index * 3u + 1u),
+        load_texel(instance_index * 3u + 2u)
+    );
+#endif
+#else
+    let quad = quads.data[instance_index];
+#endif
+#endif
+
+    let xyz = vec3<i32>(i32(corner & 0x1u), i32((corner & 0x2u) >> 1u), 0);
+    out.uv = vec2<f32>(xyz.xy);
+    let relative_pos_unit = out.uv * 2.0 - 1.0;
+    let relative_pos = relative_pos_unit * quad.half_extents.xy;
+
+    let billboard = u32(quad.half_extents.w);
+    if (billboard == BILLBOARD_NONE) {
+        var rotated_pos = vec3<f32>(relative_pos, 0.0);
+        var normal = vec3<f32>(0.0, 0.0, 1.0);
+#ifdef ROTATION
+        rotated_pos = quat_rotate(quad.rotation, rotated_pos);
+        normal = quat_rotate(quad.rotation, normal);
+#endif
+        out.world_position = instance_set.model * vec4<f32>(quad.center.xyz + rotated_pos, 1.0);
+        out.world_normal = normalize((instance_set.model * vec4<f32>(normal, 0.0)).xyz);
+        out.clip_position = view.view_proj * out.world_position;
+    } else {
+        let world_center = instance_set.model * vec4<f32>(quad.center.xyz, 1.0);
+        let to_camera = view.world_position - world_center.xyz;
+        // NOTE: The quad keeps the scale of the instance set
+        let scale = vec2<f32>(length(instance_set.model[0].xyz), length(instance_set.model[1].xyz));
+        out.world_normal = normalize(to_camera);
+        if (billboard == BILLBOARD_SCREEN_ALIGNED) {
+            // NOTE: Offset in clip space so that half_extents are in pixels
+            out.world_position = world_center;
+            out.clip_position = view.view_proj * world_center;
+            let offset = relative_pos * 2.0 / vec2<f32>(view.width, view.height);
+            out.clip_position = vec4<f32>(out.clip_position.xy + offset * out.clip_position.w, out.clip_position.zw);
+        } else {
+            var up: vec3<f32>;
+            var forward: vec3<f32>;
+            if (billboard == BILLBOARD_CYLINDRICAL) {
+                up = normalize(instance_set.model[1].xyz);
+                forward = to_camera - up * dot(to_camera, up);
+                // NOTE: With the camera directly above or below the quad, it faces the view
+                // direction instead, or the up vector of the camera when looking along its axis
+                if (length(forward) < 1e-4) {
+                    let view_back = view.inverse_view[2].xyz;
+                    forward = view_back - up * dot(view_back, up);
+                }
+                if (length(forward) < 1e-4) {
+                    let camera_up = view.inverse_view[1].xyz;
+                    forward = camera_up - up * dot(camera_up, up);
+                }
+                forward = normalize(forward);
+            } else {
+                let camera_up = view.inverse_view[1].xyz;
+                forward = out.world_normal;
+                up = cross(forward, normalize(cross(camera_up, forward)));
+            }
+            out.world_normal = forward;
+            let right = cross(up, forward);
+            let offset = right * relative_pos.x * scale.x + up * relative_pos.y * scale.y;
+            out.world_position = vec4<f32>(world_center.xyz + offset, 1.0);
+            out.clip_position = view.view_proj * out.world_position;
+        }
+    }
+    out.color = quad.color;
+    return out;
+}
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+#ifdef INSTANCE_BUFFER
+    return pull_vertex(instance, vertex_index);
+#else
+    return pull_vertex(vertex_index);
+#endif
+}
+
+struct DepthVertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// NOTE: Entry point of the depth-only passes of shapes without PulledShape::DEPTH_FRAGMENT
+[[stage(vertex)]]
+fn depth_vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> DepthVertexOutput {
+#ifdef INSTANCE_BUFFER
+    let out = pull_vertex(instance, vertex_index);
+#else
+    let out = pull_vertex(vertex_index);
+#endif
+    return DepthVertexOutput(out.clip_position);
+}
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] color: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
+
@@ -0,0 +1,84 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{VertexAttribute, VertexFormat},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{Cube, PulledShape};
+
+pub const SPHERES_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 4520839474180226397);
+
+/// A sphere, ray-cast in the fragment shader inside the hull of its bounding cube.
+///
+/// Fragments outside the sphere are discarded and the others write the depth of the surface, so
+/// spheres intersect correctly with each other and with other geometry.
+#[derive(Clone, Debug, Default)]
+pub struct Sphere {
+    pub color: Color,
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuSphere {
+    /// Center in `xyz` and radius in `w`.
+    pub center_radius: Vec4,
+    pub color: [f32; 4],
+}
+
+impl From<&Sphere> for GpuSphere {
+    fn from(sphere: &Sphere) -> Self {
+        Self {
+            center_radius: sphere.center.extend(sphere.radius),
+            color: sphere.color.as_rgba_f32(),
+        }
+    }
+}
+
+impl PulledShape for Sphere {
+    type Gpu = GpuSphere;
+
+    const VERTICES_PER_INSTANCE: u32 = Cube::VERTICES_PER_INSTANCE;
+    const INDEX_PATTERN: &'static [u32] = Cube::INDEX_PATTERN;
+    const DEPTH_FRAGMENT: bool = true;
+
+    fn shader() -> Handle<Shader> {
+        SPHERES_SHADER_HANDLE.typed()
+    }
+
+    fn to_gpu(&self) -> Self::Gpu {
+        GpuSphere::from(self)
+    }
+
+    fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    fn half_extents(&self) -> Vec3 {
+        Vec3::splat(self.radius)
+    }
+
+    fn raster_color(&self) -> Color {
+        self.color
+    }
+
+    fn instance_attributes() -> Vec<VertexAttribute> {
+        vec![
+            // center_radius
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            // color
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 16,
+                shader_location: 1,
+            },
+        ]
+    }
+}
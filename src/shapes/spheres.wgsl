@@ -0,0 +1,182 @@
+struct View {
+    view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
+};
+
+struct Sphere {
+    center_radius: vec4<f32>;
+    color: vec4<f32>;
+};
+
+struct Spheres {
+    data: array<Sphere>;
+};
+
+struct InstanceSet {
+    model: mat4x4<f32>;
+    inverse_model: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+#ifdef INSTANCE_BUFFER
+struct InstanceInput {
+    [[location(0)]] center_radius: vec4<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+#else
+#ifdef DATA_TEXTURE
+[[group(1), binding(0)]]
+var spheres: texture_2d<f32>;
+
+// NOTE: Must match DATA_TEXTURE_WIDTH
+let data_texture_width: u32 = 2048u;
+
+fn load_texel(index: u32) -> vec4<f32> {
+    return textureLoad(spheres, vec2<i32>(i32(index % data_texture_width), i32(index / data_texture_width)), 0);
+}
+#else
+[[group(1), binding(0)]]
+var<storage> spheres: Spheres;
+#endif
+#endif
+
+[[group(1), binding(1)]]
+var<uniform> instance_set: InstanceSet;
+
+#ifdef INDEXLESS
+// NOTE: Must match Cube::INDEX_PATTERN, the three faces that can face the camera after mirroring
+var<private> cube_indices: array<u32, 18> = array<u32, 18>(
+    1u, 5u, 7u, 3u, 1u, 7u,
+    3u, 7u, 6u, 3u, 6u, 2u,
+    5u, 4u, 6u, 7u, 5u, 6u,
+);
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    // NOTE: The ray is cast in the space of the instance set so that it can be scaled
+    [[location(0)]] set_position: vec3<f32>;
+    [[location(1), interpolate(flat)]] center_radius: vec4<f32>;
+    [[location(2), interpolate(flat)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(
+#ifdef INSTANCE_BUFFER
+    instance: InstanceInput,
+#endif
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+#ifdef INDEXLESS
+    let instance_index = vertex_index / 18u;
+    let corner = cube_indices[vertex_index % 18u];
+#else
+    let instance_index = vertex_index >> 3u;
+    let corner = vertex_index & 0x7u;
+#endif
+#ifdef INSTANCE_BUFFER
+    let sphere = Sphere(instance.center_radius, instance.color);
+#else
+#ifdef DATA_TEXTURE
+    let sphere = Sphere(load_texel(instance_index * 2u), load_texel(instance_index * 2u + 1u));
+#else
+    let sphere = spheres.data[instance_index];
+#endif
+#endif
+
+    // NOTE: Same branchless mirroring of the bounding cube as in cubes.wgsl
+    let set_camera_pos = instance_set.inverse_model * vec4<f32>(view.world_position, 1.0);
+    let local_camera_pos = set_camera_pos.xyz - sphere.center_radius.xyz;
+    let mirror_mask = u32(local_camera_pos.y < 0.0) << 2u | u32(local_camera_pos.z < 0.0) << 1u | u32(local_camera_pos.x < 0.0);
+    let vx = corner ^ mirror_mask;
+
+    let xyz = vec3<f32>(f32(vx & 0x1u), f32((vx & 0x4u) >> 2u), f32((vx & 0x2u) >> 1u));
+    let relative_pos = (xyz * 2.0 - 1.0) * sphere.center_radius.w;
+
+    out.set_position = sphere.center_radius.xyz + relative_pos;
+    out.clip_position = view.view_proj * instance_set.model * vec4<f32>(out.set_position, 1.0);
+    out.center_radius = sphere.center_radius;
+    out.color = sphere.color;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] set_position: vec3<f32>;
+    [[location(1), interpolate(flat)]] center_radius: vec4<f32>;
+    [[location(2), interpolate(flat)]] color: vec4<f32>;
+};
+
+struct Hit {
+    set_position: vec3<f32>;
+    depth: f32;
+};
+
+// NOTE: Discards the fragment if the ray from the camera misses the sphere
+fn ray_cast(in: FragmentInput) -> Hit {
+    let ray_origin = (instance_set.inverse_model * vec4<f32>(view.world_position, 1.0)).xyz;
+    let ray_direction = normalize(in.set_position - ray_origin);
+    let oc = ray_origin - in.center_radius.xyz;
+    let b = dot(oc, ray_direction);
+    let c = dot(oc, oc) - in.center_radius.w * in.center_radius.w;
+    let h = b * b - c;
+    if (h < 0.0) {
+        discard;
+    }
+    // NOTE: The hull only has the faces towards the camera, so spheres around the camera are not
+    // drawn
+    let t = -b - sqrt(h);
+    if (t < 0.0) {
+        discard;
+    }
+
+    var hit: Hit;
+    hit.set_position = ray_origin + ray_direction * t;
+    let clip_position = view.view_proj * instance_set.model * vec4<f32>(hit.set_position, 1.0);
+    // NOTE: Hits closer than the near plane are clamped to it, which is at depth 1 with reversed
+    // depth
+    if (clip_position.w <= view.near) {
+        hit.depth = 1.0;
+    } else {
+        hit.depth = clip_position.z / clip_position.w;
+    }
+    return hit;
+}
+
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> FragmentOutput {
+    let hit = ray_cast(in);
+
+    // NOTE: Normals are transformed by the inverse transpose of the model matrix
+    let set_normal = (hit.set_position - in.center_radius.xyz) / in.center_radius.w;
+    let world_normal = normalize((vec4<f32>(set_normal, 0.0) * instance_set.inverse_model).xyz);
+    let world_position = (instance_set.model * vec4<f32>(hit.set_position, 1.0)).xyz;
+    // NOTE: Lit by a light at the camera as pulled shapes are unlit
+    let light = max(dot(world_normal, normalize(view.world_position - world_position)), 0.0);
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color.rgb * (0.2 + 0.8 * light), in.color.a);
+    out.depth = hit.depth;
+    return out;
+}
+
+// NOTE: Entry point of the depth-only passes, see PulledShape::DEPTH_FRAGMENT
+[[stage(fragment)]]
+fn depth_fragment(in: FragmentInput) -> [[builtin(frag_depth)]] f32 {
+    return ray_cast(in).depth;
+}
@@ -0,0 +1,323 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::PrimitiveTopology,
+        render_graph::{self, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_phase::RenderPhase,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer,
+            BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferSize, BufferUsages,
+            CachedComputePipelineId, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            CompareFunction, ComputePipelineDescriptor, DepthBiasState, DepthStencilState,
+            FragmentState, FrontFace, LoadOp, MultisampleState, Operations, PipelineCache,
+            PolygonMode, PrimitiveState, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, ShaderStages, StencilFaceState, StencilState, TextureFormat,
+            VertexState,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewDepthTexture, ViewTarget},
+    },
+    utils::HashMap,
+};
+use bytemuck::cast_slice;
+
+use crate::VertexPullingPhaseItem;
+
+pub const SOFTWARE_RASTER_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1342288657904632197);
+
+pub(crate) fn load_shaders(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        SOFTWARE_RASTER_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("software_raster.wgsl")),
+    );
+}
+
+/// Number of pixels of a row of the visibility buffer cleared by each workgroup.
+// NOTE: Must match the workgroup size of clear in software_raster.wgsl
+pub const SOFTWARE_RASTER_CLEAR_WORKGROUP_SIZE: u32 = 64;
+
+/// Render pipeline that resolves the visibility buffer of a view into its color and depth with a
+/// fullscreen triangle, and compute pipeline that clears it before the instances are rasterized.
+pub struct SoftwareRasterPipeline {
+    pub pipeline_id: CachedRenderPipelineId,
+    pub clear_pipeline_id: CachedComputePipelineId,
+    pub layout: BindGroupLayout,
+}
+
+impl FromWorld for SoftwareRasterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("vertex_pulling_software_raster_layout"),
+                    entries: &[
+                        // Size of the view
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(
+                                    std::mem::size_of::<UVec4>() as u64
+                                ),
+                            },
+                            count: None,
+                        },
+                        // Visibility buffer
+                        BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(0),
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let clear_pipeline_id = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("vertex_pulling_software_raster_clear_pipeline".into()),
+            layout: Some(vec![layout.clone()]),
+            shader: SOFTWARE_RASTER_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point: "clear".into(),
+        });
+        let pipeline_id = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("vertex_pulling_software_raster_pipeline".into()),
+            layout: Some(vec![layout.clone()]),
+            vertex: VertexState {
+                shader: SOFTWARE_RASTER_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: SOFTWARE_RASTER_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState {
+                    front: StencilFaceState::IGNORE,
+                    back: StencilFaceState::IGNORE,
+                    read_mask: 0,
+                    write_mask: 0,
+                },
+                bias: DepthBiasState {
+                    constant: 0,
+                    slope_scale: 0.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState {
+                count: Msaa::default().samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        Self {
+            pipeline_id,
+            clear_pipeline_id,
+            layout,
+        }
+    }
+}
+
+/// Visibility buffer of a view that the culling compute shader rasterizes the instances smaller
+/// than [`LodSettings::software_raster_size`](crate::LodSettings::software_raster_size) into.
+///
+/// Each pixel is a `u32` packing the top 20 bits of the reversed depth above an RGB444 color, so
+/// that `atomicMax` keeps the nearest instance. Zero is an empty pixel. The buffer is cleared at
+/// the start of each frame by [`VertexPullingCullNode`](crate::VertexPullingCullNode).
+pub struct SoftwareRasterTarget {
+    /// Size of the view, and so of the visibility buffer.
+    pub size: UVec2,
+    pub visibility_buffer: Buffer,
+    pub uniform_buffer: Buffer,
+    /// Binds the uniform and visibility buffers to clear and resolve them.
+    pub bind_group: BindGroup,
+}
+
+impl SoftwareRasterTarget {
+    pub fn new(
+        render_device: &RenderDevice,
+        size: UVec2,
+        pipeline: &SoftwareRasterPipeline,
+    ) -> Self {
+        let visibility_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("vertex_pulling_visibility_buffer"),
+            size: (size.x * size.y) as u64 * std::mem::size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("vertex_pulling_software_raster_uniform_buffer"),
+            contents: cast_slice(&[size.x, size.y, 0, 0]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("vertex_pulling_software_raster_bind_group"),
+            layout: &pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: visibility_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            size,
+            visibility_buffer,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+}
+
+/// Visibility buffers of all views.
+#[derive(Default)]
+pub struct SoftwareRasterTargets {
+    pub targets: HashMap<Entity, SoftwareRasterTarget>,
+}
+
+pub fn prepare_software_raster_targets(
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<VertexPullingPhaseItem>>>,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<SoftwareRasterPipeline>,
+    mut software_raster_targets: ResMut<SoftwareRasterTargets>,
+) {
+    software_raster_targets
+        .targets
+        .retain(|entity, _| views.get(*entity).is_ok());
+    for (entity, view) in views.iter() {
+        let size = UVec2::new(view.width.max(1), view.height.max(1));
+        if software_raster_targets
+            .targets
+            .get(&entity)
+            .map_or(true, |target| target.size != size)
+        {
+            software_raster_targets.targets.insert(
+                entity,
+                SoftwareRasterTarget::new(&render_device, size, &pipeline),
+            );
+        }
+    }
+}
+
+/// Resolves the visibility buffer of a view into its color and depth after the instances are
+/// drawn.
+pub struct VertexPullingSoftwareRasterNode {
+    query: QueryState<(&'static ViewTarget, &'static ViewDepthTexture), With<ExtractedView>>,
+}
+
+impl VertexPullingSoftwareRasterNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl render_graph::Node for VertexPullingSoftwareRasterNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(
+            VertexPullingSoftwareRasterNode::IN_VIEW,
+            SlotType::Entity,
+        )]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (target, depth) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()), // No window
+        };
+        let software_raster_target = match world
+            .resource::<SoftwareRasterTargets>()
+            .targets
+            .get(&view_entity)
+        {
+            Some(software_raster_target) => software_raster_target,
+            None => return Ok(()),
+        };
+        let pipeline = match world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(world.resource::<SoftwareRasterPipeline>().pipeline_id)
+        {
+            Some(pipeline) => pipeline,
+            None => return Ok(()),
+        };
+
+        #[cfg(feature = "trace")]
+        let _vertex_pulling_software_raster_span =
+            info_span!("vertex_pulling_software_raster").entered();
+        let pass_descriptor = RenderPassDescriptor {
+            label: Some("vertex_pulling_software_raster_pass"),
+            color_attachments: &[target.get_color_attachment(Operations {
+                load: LoadOp::Load,
+                store: true,
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        };
+
+        let mut render_pass = render_context
+            .command_encoder
+            .begin_render_pass(&pass_descriptor);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &software_raster_target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
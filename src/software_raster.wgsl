@@ -0,0 +1,56 @@
+struct SoftwareRaster {
+    size: vec4<u32>;
+};
+
+// NOTE: Must match the packing in cull.wgsl
+struct Visibility {
+    data: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> raster: SoftwareRaster;
+
+[[group(0), binding(1)]]
+var<storage, read_write> visibility: Visibility;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// NOTE: A single triangle covering the whole view
+[[stage(vertex)]]
+fn vertex([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u)) * 2.0;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+[[stage(fragment)]]
+fn fragment([[builtin(position)]] position: vec4<f32>) -> FragmentOutput {
+    let index = u32(position.y) * raster.size.x + u32(position.x);
+    let packed = visibility.data[index];
+    if (packed == 0u) {
+        discard;
+    }
+
+    var out: FragmentOutput;
+    let rgb = vec3<u32>((packed >> 8u) & 0xfu, (packed >> 4u) & 0xfu, packed & 0xfu);
+    out.color = vec4<f32>(vec3<f32>(rgb) / 15.0, 1.0);
+    out.depth = bitcast<f32>((packed & 0xfffff000u) >> 1u);
+    return out;
+}
+
+// NOTE: Dispatched with one row of workgroups per row of pixels, must match
+// SOFTWARE_RASTER_CLEAR_WORKGROUP_SIZE
+[[stage(compute), workgroup_size(64)]]
+fn clear([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    if (global_id.x < raster.size.x) {
+        visibility.data[global_id.y * raster.size.x + global_id.x] = 0u;
+    }
+}
@@ -0,0 +1,62 @@
+/// Returns the indices of `keys` in ascending key order.
+///
+/// This is a stable least significant digit radix sort over 8-bit digits, so it takes four
+/// linear passes regardless of the number of keys.
+pub fn radix_sort_indices(keys: &[u32]) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..keys.len() as u32).collect();
+    let mut sorted = vec![0; keys.len()];
+    for shift in (0..32).step_by(8) {
+        let mut offsets = [0usize; 256];
+        for &index in order.iter() {
+            offsets[(keys[index as usize] >> shift & 0xff) as usize] += 1;
+        }
+        let mut offset = 0;
+        for count in offsets.iter_mut() {
+            let digit_count = *count;
+            *count = offset;
+            offset += digit_count;
+        }
+        for &index in order.iter() {
+            let digit = (keys[index as usize] >> shift & 0xff) as usize;
+            sorted[offsets[digit]] = index;
+            offsets[digit] += 1;
+        }
+        std::mem::swap(&mut order, &mut sorted);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_keys_keep_their_order() {
+        let keys = [3, 1, 3, 0, 1, 3];
+        assert_eq!(radix_sort_indices(&keys), vec![3, 1, 4, 0, 2, 5]);
+    }
+
+    #[test]
+    fn sorts_by_every_byte() {
+        let keys = [
+            0xff00_0000,
+            0x0000_00ff,
+            0x0100_0000,
+            0x00ff_0000,
+            0x0000_ff00,
+            0xff00_0001,
+            u32::MAX,
+            0,
+        ];
+        let order = radix_sort_indices(&keys);
+        assert_eq!(order, vec![7, 1, 4, 3, 2, 0, 5, 6]);
+        assert!(order
+            .windows(2)
+            .all(|pair| keys[pair[0] as usize] <= keys[pair[1] as usize]));
+    }
+
+    #[test]
+    fn empty_keys() {
+        assert!(radix_sort_indices(&[]).is_empty());
+    }
+}